@@ -0,0 +1,290 @@
+use core::ptr::NonNull;
+use std::alloc::{self, Layout};
+use std::fmt;
+
+/// The error returned when the heap allocation for a `BlackBox` fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError {
+    layout: Layout,
+}
+
+impl AllocError {
+    /// Creating the error for the heap allocation of `layout` which failed.
+    pub fn new(layout: Layout) -> Self {
+        AllocError { layout }
+    }
+
+    /// The layout of the heap allocation which failed.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "memory allocation of {} bytes failed", self.layout.size())
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+/// Where a `BlackBox` gets its heap memory from and gives it back to. It's the
+/// stable Rust version of the (still unstable) `std::alloc::Allocator`, so the
+/// large data sets can live in arenas, huge-page pools or per-request allocators.
+///
+/// `BlackBox` never asks for a zero-sized layout, zero-sized types get a dangling
+/// pointer and are never deallocated, the same as `Box`. Other callers can, as
+/// the trait is public, so an implementation must handle them too.
+///
+/// # Safety
+///
+/// - A memory block returned by `allocate` or `allocate_zeroed` must be valid for
+///   reads and writes of `layout`, and stay valid until it's passed to
+///   `deallocate`, or the allocator can't be used any more, whichever comes
+///   first: the allocator value (and all its clones) is dropped, or the borrow of
+///   a borrowed allocator like `&BlackBoxArena` ends. Moving the allocator value
+///   doesn't count.
+/// - `allocate_zeroed` must return a block filled with `0` bytes.
+pub unsafe trait Allocator {
+    /// Allocates a memory block for `layout`.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// Same with `allocate()`, but the memory block is filled with `0` bytes.
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let block = self.allocate(layout)?;
+
+        // Safety: `allocate` just returned a block valid for writes of `layout`.
+        unsafe { block.as_ptr().write_bytes(0, layout.size()) };
+        Ok(block)
+    }
+
+    /// Gives the memory block back.
+    ///
+    /// # Safety
+    ///
+    /// `block` must come from this allocator with the same `layout`, and must not
+    /// be used afterwards.
+    unsafe fn deallocate(&self, block: NonNull<u8>, layout: Layout);
+}
+
+/// The global allocator (`#[global_allocator]` or the system one), the default
+/// allocator of `BlackBox`. It's the same allocator `Box` uses, that's why a
+/// `BlackBox<T, Global>` converts to and from `Box<T>` without copying.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Global;
+
+/// `std::alloc::alloc` doesn't accept a zero-sized layout, so those get a dangling
+/// (but aligned) pointer which is never deallocated.
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+
+        // Safety: the layout is not zero-sized.
+        NonNull::new(unsafe { alloc::alloc(layout) }).ok_or(AllocError::new(layout))
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+
+        // Safety: the layout is not zero-sized.
+        NonNull::new(unsafe { alloc::alloc_zeroed(layout) }).ok_or(AllocError::new(layout))
+    }
+
+    unsafe fn deallocate(&self, block: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            alloc::dealloc(block.as_ptr(), layout)
+        }
+    }
+}
+
+/// A non-null pointer aligned for `layout`, for the zero-sized layouts.
+fn dangling(layout: Layout) -> NonNull<u8> {
+    // Safety: the alignment is never 0.
+    unsafe { NonNull::new_unchecked(layout.align() as *mut u8) }
+}
+
+/// Lets many `BlackBox` share one allocator, e.g. `BlackBox<T, &Arena>`.
+#[cfg(feature = "allocator-api")]
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        (**self).allocate(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        (**self).allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, block: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(block, layout)
+    }
+}
+
+/// Allocates the heap memory for a `T` from `alloc`, zero-sized types get a
+/// dangling pointer.
+pub(crate) fn allocate<T, A: Allocator + ?Sized>(
+    alloc: &A,
+    zeroed: bool,
+) -> Result<NonNull<T>, AllocError> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        return Ok(NonNull::dangling());
+    }
+
+    let block = if zeroed {
+        alloc.allocate_zeroed(layout)?
+    } else {
+        alloc.allocate(layout)?
+    };
+    Ok(block.cast())
+}
+
+/// Gives the heap memory of `layout` back to `alloc`, unless it's zero-sized.
+///
+/// # Safety
+///
+/// Same with `Allocator::deallocate`.
+pub(crate) unsafe fn deallocate<A: Allocator + ?Sized>(
+    alloc: &A,
+    block: NonNull<u8>,
+    layout: Layout,
+) {
+    if layout.size() != 0 {
+        alloc.deallocate(block, layout)
+    }
+}
+
+/// Aborts the process the same way `Box::new` does when it's out of memory.
+pub(crate) fn handle_alloc_error(error: AllocError) -> ! {
+    alloc::handle_alloc_error(error.layout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "allocator-api")]
+    use crate::BlackBox;
+    #[cfg(feature = "allocator-api")]
+    use std::cell::Cell;
+
+    /// Counts the memory going in and out, and fails once `limit` bytes are in use.
+    #[cfg(feature = "allocator-api")]
+    #[derive(Default)]
+    struct CountingAllocator {
+        allocations: Cell<usize>,
+        deallocations: Cell<usize>,
+        bytes_in_use: Cell<usize>,
+        limit: Option<usize>,
+    }
+
+    #[cfg(feature = "allocator-api")]
+    unsafe impl Allocator for CountingAllocator {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            let bytes_in_use = self.bytes_in_use.get() + layout.size();
+            if self.limit.is_some_and(|limit| bytes_in_use > limit) {
+                return Err(AllocError::new(layout));
+            }
+
+            self.allocations.set(self.allocations.get() + 1);
+            self.bytes_in_use.set(bytes_in_use);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, block: NonNull<u8>, layout: Layout) {
+            self.deallocations.set(self.deallocations.get() + 1);
+            self.bytes_in_use.set(self.bytes_in_use.get() - layout.size());
+            Global.deallocate(block, layout)
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "allocator-api")]
+    fn drop_returns_memory_to_the_right_allocator() {
+        let first = CountingAllocator::default();
+        let second = CountingAllocator::default();
+
+        {
+            let mut first_box = BlackBox::new_in("Very large string data".to_owned(), &first);
+            let second_box = BlackBox::new_in([0u64; 16], &second);
+
+            assert_eq!(first_box.len(), 22);
+            assert_eq!(second_box.len(), 16);
+            assert_eq!(first.bytes_in_use.get(), std::mem::size_of::<String>());
+            assert_eq!(second.bytes_in_use.get(), 128);
+
+            first_box.take();
+            assert_eq!(first.bytes_in_use.get(), 0);
+            first_box.replace("a".to_owned());
+            assert_eq!(first.allocations.get(), 2);
+        }
+
+        assert_eq!(
+            (first.allocations.get(), first.deallocations.get(), first.bytes_in_use.get()),
+            (2, 2, 0)
+        );
+        assert_eq!(
+            (second.allocations.get(), second.deallocations.get(), second.bytes_in_use.get()),
+            (1, 1, 0)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "allocator-api")]
+    fn clone_allocates_from_the_same_allocator() {
+        let allocator = CountingAllocator::default();
+
+        let black_box = BlackBox::new_in(vec![1, 2, 3], &allocator);
+        let cloned = black_box.clone();
+        assert_eq!(*cloned, [1, 2, 3]);
+        assert_eq!(allocator.allocations.get(), 2);
+
+        drop((black_box, cloned));
+        assert_eq!(allocator.deallocations.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "allocator-api")]
+    fn try_new_in_fails_once_the_limit_is_reached() {
+        let allocator = CountingAllocator {
+            limit: Some(1024),
+            ..CountingAllocator::default()
+        };
+
+        let first = BlackBox::try_new_in([1u8; 1000], &allocator);
+        let second = BlackBox::try_new_in([2u8; 1000], &allocator);
+        assert_eq!(first.unwrap()[999], 1);
+        assert_eq!(second.unwrap_err().layout(), Layout::new::<[u8; 1000]>());
+
+        // The first box is gone, so there is room again.
+        let zeroed = BlackBox::<[u8; 1000], _>::try_new_uninit_in(&allocator).unwrap();
+        assert!(!zeroed.is_null());
+    }
+
+    #[test]
+    #[cfg(feature = "allocator-api")]
+    fn zero_sized_types_never_allocate() {
+        let allocator = CountingAllocator {
+            limit: Some(0),
+            ..CountingAllocator::default()
+        };
+
+        let unit = BlackBox::new_in((), &allocator);
+        drop(unit);
+        assert_eq!((allocator.allocations.get(), allocator.deallocations.get()), (0, 0));
+    }
+
+    #[test]
+    fn global_handles_zero_sized_layouts() {
+        let layout = Layout::from_size_align(0, 64).unwrap();
+
+        for block in [Global.allocate(layout), Global.allocate_zeroed(layout)] {
+            let block = block.unwrap();
+            assert_eq!(block.as_ptr() as usize % 64, 0);
+            // Safety: the block comes from `Global` with the same layout.
+            unsafe { Global.deallocate(block, layout) };
+        }
+    }
+}
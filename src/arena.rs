@@ -0,0 +1,338 @@
+#[cfg(feature = "allocator-api")]
+use crate::allocator::Allocator;
+use crate::allocator::{self, AllocError};
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use std::alloc::{self as std_alloc, Layout};
+use std::{cmp, fmt, mem};
+
+/// The size of the first chunk, each new chunk doubles the previous one.
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// Every chunk is at least this aligned.
+const CHUNK_ALIGN: usize = 16;
+
+/// A bump allocator for many `BlackBox`-like values which die together.
+///
+/// Every `alloc()` carves the heap value out of a large chunk, which is just a
+/// pointer bump, and hands out an `ArenaBlackBox` of one pointer width. Nothing is
+/// freed one by one: all the chunks are freed at once when the arena is dropped,
+/// and `reset()` makes the memory reusable for the next batch (e.g. the next
+/// request) without giving it back to the global allocator.
+///
+/// Destructors are optional: `alloc()` remembers the destructor of the heap value
+/// (if it has one), `alloc_no_drop()` doesn't. The remembered destructors run in
+/// the reverse order of allocation, on `reset()` or when the arena is dropped.
+///
+/// With the `allocator-api` feature, the arena also implements `Allocator`, so
+/// `BlackBox::new_in(value, &arena)` works as well, in which case the `BlackBox`
+/// runs the destructor itself.
+pub struct BlackBoxArena {
+    /// All the chunks, the last one is where the next allocation comes from.
+    chunks: RefCell<Vec<Chunk>>,
+    /// How many bytes of the last chunk are in use.
+    used: Cell<usize>,
+    /// The destructors to run, in allocation order.
+    destructors: RefCell<Vec<Destructor>>,
+    /// The size of the first chunk.
+    chunk_size: usize,
+}
+
+struct Chunk {
+    start: NonNull<u8>,
+    layout: Layout,
+}
+
+struct Destructor {
+    value: NonNull<u8>,
+    drop_value: unsafe fn(NonNull<u8>),
+}
+
+/// Type-erased `drop_in_place::<T>`.
+unsafe fn drop_value<T>(value: NonNull<u8>) {
+    ptr::drop_in_place(value.cast::<T>().as_ptr())
+}
+
+impl BlackBoxArena {
+    /// Creating an empty arena, the first chunk is allocated on the first `alloc()`.
+    pub fn new() -> Self {
+        BlackBoxArena::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Same with `new()`, but the first chunk is `chunk_size` bytes (or larger if
+    /// the first heap value doesn't fit).
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        BlackBoxArena {
+            chunks: RefCell::new(Vec::new()),
+            used: Cell::new(0),
+            destructors: RefCell::new(Vec::new()),
+            chunk_size: cmp::max(chunk_size, 1),
+        }
+    }
+
+    /// Moves `value` into the arena. Its destructor runs on `reset()` or when the
+    /// arena is dropped.
+    ///
+    /// `T` must be `'static`, as the destructor runs later than the handle's
+    /// lifetime tells, use `alloc_no_drop()` for values holding references.
+    pub fn alloc<T: 'static>(&self, value: T) -> ArenaBlackBox<'_, T> {
+        let handle = self.alloc_no_drop(value);
+
+        if mem::needs_drop::<T>() {
+            self.destructors.borrow_mut().push(Destructor {
+                value: handle.heap_value.cast(),
+                drop_value: drop_value::<T>,
+            });
+        }
+
+        handle
+    }
+
+    /// Moves `value` into the arena, its destructor never runs. That's fine for
+    /// plain data, otherwise whatever the destructor would free is leaked.
+    pub fn alloc_no_drop<T>(&self, value: T) -> ArenaBlackBox<'_, T> {
+        let heap_value = match self.alloc_layout(Layout::new::<T>()) {
+            Ok(block) => block.cast::<T>(),
+            Err(error) => allocator::handle_alloc_error(error),
+        };
+
+        // Safety: the block is freshly allocated for a `T`.
+        unsafe { heap_value.as_ptr().write(value) };
+
+        ArenaBlackBox {
+            heap_value,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Runs the remembered destructors (in reverse order) and makes all the memory
+    /// reusable. Only the largest chunk is kept, so the next batch usually fits in
+    /// one chunk. `&mut self` makes sure no `ArenaBlackBox` is alive.
+    pub fn reset(&mut self) {
+        self.run_destructors();
+
+        let chunks = self.chunks.get_mut();
+        if let Some(largest) = chunks.pop() {
+            for chunk in chunks.drain(..) {
+                // Safety: nothing in the arena is alive any more.
+                unsafe { std_alloc::dealloc(chunk.start.as_ptr(), chunk.layout) };
+            }
+            chunks.push(largest);
+        }
+        self.used.set(0);
+    }
+
+    /// The total size of all the chunks, in bytes.
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().iter().map(|chunk| chunk.layout.size()).sum()
+    }
+
+    fn run_destructors(&mut self) {
+        // Later values may refer to earlier ones, so drop the later ones first.
+        while let Some(destructor) = self.destructors.get_mut().pop() {
+            // Safety: the value is alive and no handle to it exists any more.
+            unsafe { (destructor.drop_value)(destructor.value) };
+        }
+    }
+
+    /// Bumps a block for `layout` out of the last chunk, or out of a new chunk if
+    /// it doesn't fit.
+    fn alloc_layout(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            // Safety: the alignment is never 0.
+            return Ok(unsafe { NonNull::new_unchecked(layout.align() as *mut u8) });
+        }
+
+        let mut chunks = self.chunks.borrow_mut();
+        if let Some(chunk) = chunks.last() {
+            let used = self.used.get();
+            let address = chunk.start.as_ptr() as usize + used;
+            let padding = address.wrapping_neg() & (layout.align() - 1);
+
+            if chunk.layout.size() - used >= padding + layout.size() {
+                self.used.set(used + padding + layout.size());
+                // Safety: the block is within the chunk.
+                let block = unsafe { chunk.start.as_ptr().add(used + padding) };
+                return Ok(unsafe { NonNull::new_unchecked(block) });
+            }
+        }
+
+        // Grow by doubling, so the number of chunks stays small.
+        let size = chunks.last().map_or(self.chunk_size, |chunk| chunk.layout.size() * 2);
+        let chunk_layout = Layout::from_size_align(
+            cmp::max(size, layout.size()),
+            cmp::max(layout.align(), CHUNK_ALIGN),
+        )
+        .map_err(|_| AllocError::new(layout))?;
+
+        // Safety: `chunk_layout` is never zero-sized.
+        let start = NonNull::new(unsafe { std_alloc::alloc(chunk_layout) })
+            .ok_or(AllocError::new(chunk_layout))?;
+        chunks.push(Chunk {
+            start,
+            layout: chunk_layout,
+        });
+        self.used.set(layout.size());
+
+        Ok(start)
+    }
+}
+
+impl Default for BlackBoxArena {
+    fn default() -> Self {
+        BlackBoxArena::new()
+    }
+}
+
+impl fmt::Debug for BlackBoxArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlackBoxArena")
+            .field("chunks", &self.chunks.borrow().len())
+            .field("capacity", &self.capacity())
+            .field("destructors", &self.destructors.borrow().len())
+            .finish()
+    }
+}
+
+/// Runs the remembered destructors, then frees all the chunks at once.
+impl Drop for BlackBoxArena {
+    fn drop(&mut self) {
+        self.run_destructors();
+
+        for chunk in self.chunks.get_mut().drain(..) {
+            unsafe { std_alloc::dealloc(chunk.start.as_ptr(), chunk.layout) };
+        }
+    }
+}
+
+/// Every block lives as long as the arena, so `deallocate` has nothing to do, the
+/// memory comes back on `reset()` or when the arena is dropped. Both need the
+/// arena itself rather than a borrow of it, so each `&BlackBoxArena` allocator has
+/// ended by then, as the `Allocator` contract allows.
+#[cfg(feature = "allocator-api")]
+unsafe impl Allocator for BlackBoxArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.alloc_layout(layout)
+    }
+
+    unsafe fn deallocate(&self, _block: NonNull<u8>, _layout: Layout) {}
+}
+
+/// The handle returned by `BlackBoxArena::alloc()`, one pointer wide, which can't
+/// outlive its arena. Dropping the handle does nothing, the heap value belongs to
+/// the arena.
+pub struct ArenaBlackBox<'arena, T> {
+    heap_value: NonNull<T>,
+    _marker: PhantomData<&'arena mut T>,
+}
+
+impl<T> std::ops::Deref for ArenaBlackBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the heap value lives as long as the arena, which outlives `self`.
+        unsafe { self.heap_value.as_ref() }
+    }
+}
+
+impl<T> std::ops::DerefMut for ArenaBlackBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: every handle points to its own heap value.
+        unsafe { self.heap_value.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaBlackBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ArenaBlackBox").field(&**self).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "allocator-api")]
+    use crate::BlackBox;
+    use std::rc::Rc;
+
+    /// Records its id into the shared log when it's dropped.
+    struct Noisy(u32, Rc<RefCell<Vec<u32>>>);
+
+    impl Drop for Noisy {
+        fn drop(&mut self) {
+            self.1.borrow_mut().push(self.0);
+        }
+    }
+
+    #[test]
+    fn handle_is_one_pointer_wide() {
+        assert_eq!(mem::size_of::<ArenaBlackBox<String>>(), mem::size_of::<usize>());
+        assert_eq!(mem::size_of::<Option<ArenaBlackBox<String>>>(), mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn values_are_bumped_out_of_few_chunks() {
+        let arena = BlackBoxArena::with_chunk_size(64);
+
+        let handles: Vec<ArenaBlackBox<u64>> = (0..100).map(|i| arena.alloc(i)).collect();
+        let mut byte = arena.alloc(1u8);
+        let aligned = arena.alloc(0u128);
+        *byte += 1;
+
+        assert_eq!(handles.iter().map(|handle| **handle).sum::<u64>(), 4950);
+        assert_eq!(*byte, 2);
+        assert_eq!(&*aligned as *const u128 as usize % mem::align_of::<u128>(), 0);
+        // 64 + 128 + 256 + 512 bytes are enough for 100 `u64` and a bit more.
+        assert_eq!(arena.chunks.borrow().len(), 4);
+    }
+
+    #[test]
+    fn destructors_run_in_reverse_order_on_reset_and_drop() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut arena = BlackBoxArena::new();
+
+        for id in 0..3 {
+            arena.alloc(Noisy(id, log.clone()));
+        }
+        arena.alloc_no_drop(Noisy(99, log.clone()));
+        assert!(log.borrow().is_empty());
+
+        arena.reset();
+        assert_eq!(*log.borrow(), [2, 1, 0]);
+
+        arena.alloc(Noisy(3, log.clone()));
+        arena.alloc(Noisy(4, log.clone()));
+        drop(arena);
+        assert_eq!(*log.borrow(), [2, 1, 0, 4, 3]);
+    }
+
+    #[test]
+    fn reset_keeps_only_the_largest_chunk() {
+        let mut arena = BlackBoxArena::with_chunk_size(16);
+        for i in 0..64u64 {
+            arena.alloc(i);
+        }
+        let largest = arena.chunks.borrow().last().unwrap().layout.size();
+
+        arena.reset();
+        assert_eq!(arena.capacity(), largest);
+
+        let first = arena.alloc(1u64);
+        assert_eq!(&*first as *const u64 as *mut u8, arena.chunks.borrow()[0].start.as_ptr());
+    }
+
+    #[test]
+    #[cfg(feature = "allocator-api")]
+    fn black_box_can_allocate_from_the_arena() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let arena = BlackBoxArena::new();
+
+        let mut black_box = BlackBox::new_in(Noisy(1, log.clone()), &arena);
+        black_box.0 += 1;
+        drop(black_box);
+
+        assert_eq!(*log.borrow(), [2]);
+        assert_eq!(arena.capacity(), DEFAULT_CHUNK_SIZE);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::BlackBox;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use std::fmt;
+
+/// A `BlackBox` whose pointer can be swapped atomically, so one thread can publish
+/// a new large data set (e.g. a configuration snapshot) while others pick it up.
+///
+/// It keeps the single-pointer representation of `BlackBox`, just in an
+/// `AtomicPtr<T>`, and the null pointer is still the **null pointer** state.
+/// Every value moving in or out is a `BlackBox<T>`, so the old heap value can
+/// always be reclaimed safely, it's just a `BlackBox` again:
+///
+/// ```
+/// use raw_pointer_struct_in_rust::{AtomicBlackBox, BlackBox};
+///
+/// let config = AtomicBlackBox::new(BlackBox::new("v1".to_owned()));
+/// let old = config.swap(BlackBox::new("v2".to_owned()));
+/// assert_eq!(*old, "v1");
+/// ```
+///
+/// Moving a heap value in or out always uses `AcqRel` (`Acquire` for a failed
+/// `compare_exchange()`), as the pointer carries the ownership of the heap value:
+/// whoever gets a `BlackBox` out must see everything written to its heap value
+/// before it was put in. That's why only `load()` takes an `Ordering`.
+///
+/// `load()` only returns the raw pointer: another thread may swap the heap value
+/// out and drop it at any time, so reading through that pointer is only safe as
+/// long as nobody frees the old heap values while readers may still use them.
+pub struct AtomicBlackBox<T> {
+    large_data_on_the_heap: AtomicPtr<T>,
+}
+
+impl<T> AtomicBlackBox<T> {
+    /// Creating instance which takes over the heap value of `black_box`.
+    pub fn new(black_box: BlackBox<T>) -> Self {
+        AtomicBlackBox {
+            large_data_on_the_heap: AtomicPtr::new(black_box.into_raw()),
+        }
+    }
+
+    /// Creating an instance which holds a **null pointer**.
+    pub const fn empty() -> Self {
+        AtomicBlackBox {
+            large_data_on_the_heap: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the raw pointer to the current heap value, or a null raw pointer.
+    /// See the type level doc before reading through it.
+    pub fn load(&self, order: Ordering) -> *mut T {
+        self.large_data_on_the_heap.load(order)
+    }
+
+    /// Replaces the heap value with the one of `new`, and returns the old one.
+    pub fn swap(&self, new: BlackBox<T>) -> BlackBox<T> {
+        let old = self
+            .large_data_on_the_heap
+            .swap(new.into_raw(), Ordering::AcqRel);
+
+        // Safety: the old pointer is swapped out, so nobody else owns it any more,
+        // and `Acquire` makes its heap value visible to this thread.
+        unsafe { BlackBox::from_raw(old) }
+    }
+
+    /// Replaces the heap value with the one of `new`, and drops the old one.
+    pub fn store(&self, new: BlackBox<T>) {
+        drop(self.swap(new));
+    }
+
+    /// Takes the heap value out, leaving a **null pointer** behind.
+    pub fn take(&self) -> BlackBox<T> {
+        self.swap(BlackBox::empty())
+    }
+
+    /// Replaces the heap value with the one of `new` if the current pointer is still
+    /// `current` (usually from an earlier `load()`).
+    ///
+    /// Returns the old heap value on success, or hands `new` back on failure.
+    pub fn compare_exchange(
+        &self,
+        current: *mut T,
+        new: BlackBox<T>,
+    ) -> Result<BlackBox<T>, BlackBox<T>> {
+        let new = new.into_raw();
+
+        // Safety: on success the old pointer is swapped out, so nobody else owns it
+        // any more. On failure `new` was never published.
+        match self.large_data_on_the_heap.compare_exchange(
+            current,
+            new,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(old) => Ok(unsafe { BlackBox::from_raw(old) }),
+            Err(_) => Err(unsafe { BlackBox::from_raw(new) }),
+        }
+    }
+
+    /// Returns the mutable heap value reference, `&mut self` proves no other thread
+    /// can look at it right now.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // Safety: the heap value is owned by `self`.
+        unsafe { self.large_data_on_the_heap.get_mut().as_mut() }
+    }
+
+    /// Consumes the `AtomicBlackBox` and returns its heap value.
+    pub fn into_inner(self) -> BlackBox<T> {
+        self.take()
+    }
+}
+
+impl<T> Default for AtomicBlackBox<T> {
+    fn default() -> Self {
+        AtomicBlackBox::empty()
+    }
+}
+
+impl<T> From<BlackBox<T>> for AtomicBlackBox<T> {
+    fn from(black_box: BlackBox<T>) -> Self {
+        AtomicBlackBox::new(black_box)
+    }
+}
+
+impl<T> fmt::Debug for AtomicBlackBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicBlackBox")
+            .field("large_data_on_the_heap", &self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// Moving an `AtomicBlackBox` moves its heap value, like `BlackBox`. Sharing one
+/// lets other threads swap the heap value out (`Send`) and read it (`Sync`).
+unsafe impl<T: Send> Send for AtomicBlackBox<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicBlackBox<T> {}
+
+impl<T> Drop for AtomicBlackBox<T> {
+    fn drop(&mut self) {
+        // Safety: `&mut self` means no other thread can see the heap value any more.
+        drop(unsafe { BlackBox::from_raw(*self.large_data_on_the_heap.get_mut()) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[test]
+    fn swap_store_and_take() {
+        let atomic = AtomicBlackBox::new(BlackBox::new(1));
+
+        assert_eq!(unsafe { *atomic.load(Ordering::Acquire) }, 1);
+        assert_eq!(*atomic.swap(BlackBox::new(2)), 1);
+
+        atomic.store(BlackBox::new(3));
+        assert_eq!(*atomic.take(), 3);
+        assert!(atomic.load(Ordering::Acquire).is_null());
+        assert!(atomic.take().is_null());
+    }
+
+    #[test]
+    fn compare_exchange_hands_back_the_loser() {
+        let mut atomic = AtomicBlackBox::new(BlackBox::new("v1".to_owned()));
+        let current = atomic.load(Ordering::Acquire);
+
+        let old = atomic.compare_exchange(current, BlackBox::new("v2".to_owned()));
+        assert_eq!(*old.unwrap(), "v1");
+
+        // `current` is stale now.
+        let rejected = atomic.compare_exchange(current, BlackBox::new("v3".to_owned()));
+        assert_eq!(*rejected.unwrap_err(), "v3");
+
+        atomic.get_mut().unwrap().push('!');
+        assert_eq!(*atomic.into_inner(), "v2!");
+    }
+
+    #[test]
+    fn every_published_value_is_dropped_exactly_once() {
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let atomic = AtomicBlackBox::new(BlackBox::new(Counted(dropped.clone())));
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        atomic.store(BlackBox::new(Counted(dropped.clone())));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(dropped.load(Ordering::SeqCst), 4000);
+        drop(atomic);
+        assert_eq!(dropped.load(Ordering::SeqCst), 4001);
+    }
+}
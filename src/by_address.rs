@@ -0,0 +1,106 @@
+use crate::{Allocator, BlackBox};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// Compares and hashes a `BlackBox` by its heap address rather than its heap
+/// value, so two `BlackBox` holding equal values are still two different keys:
+///
+/// ```
+/// use raw_pointer_struct_in_rust::{BlackBox, ByAddress};
+/// use std::collections::HashSet;
+///
+/// let (first, second) = (BlackBox::new(1), BlackBox::new(1));
+/// assert!(first == second);
+///
+/// let boxes: HashSet<_> = vec![ByAddress(first), ByAddress(second)].into_iter().collect();
+/// assert_eq!(boxes.len(), 2);
+/// ```
+///
+/// All the **null pointer** are the same key. The order is the order of the heap
+/// addresses, which is stable but has nothing to do with the heap values.
+#[derive(Debug, Default)]
+pub struct ByAddress<B>(pub B);
+
+impl<B> ByAddress<B> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> B {
+        self.0
+    }
+}
+
+impl<B> std::ops::Deref for ByAddress<B> {
+    type Target = B;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: ?Sized, A: Allocator> PartialEq for ByAddress<BlackBox<T, A>> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Eq for ByAddress<BlackBox<T, A>> {}
+
+impl<T: ?Sized, A: Allocator> PartialOrd for ByAddress<BlackBox<T, A>> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Ord for ByAddress<BlackBox<T, A>> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.address().cmp(&other.0.address())
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Hash for ByAddress<BlackBox<T, A>> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.address().hash(state);
+    }
+}
+
+impl<T: ?Sized, A: Allocator> From<BlackBox<T, A>> for ByAddress<BlackBox<T, A>> {
+    fn from(black_box: BlackBox<T, A>) -> Self {
+        ByAddress(black_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeSet, HashSet};
+    use std::ptr;
+
+    #[test]
+    fn equal_values_at_different_addresses_are_different_keys() {
+        let first = ByAddress(BlackBox::new("same".to_owned()));
+        let second = ByAddress::from(BlackBox::new("same".to_owned()));
+        let address = &**first as *const String;
+
+        assert!(first.0 == second.0);
+        assert!(first != second);
+
+        let mut set = HashSet::new();
+        assert!(set.insert(first));
+        assert!(set.insert(second));
+        assert!(set.iter().any(|key| ptr::eq(&***key, address)));
+
+        // Null pointers are all the same key.
+        assert!(set.insert(ByAddress(BlackBox::empty())));
+        assert!(!set.insert(ByAddress(BlackBox::empty())));
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn ordered_by_heap_address() {
+        let ordered: BTreeSet<ByAddress<BlackBox<u64>>> =
+            (0..8).map(|i| ByAddress(BlackBox::new(i))).collect();
+
+        let addresses: Vec<*const u64> = ordered.iter().map(|key| &*key.0 as *const u64).collect();
+        assert!(addresses.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ByAddress(BlackBox::empty()) < *ordered.iter().next().unwrap());
+    }
+}
@@ -0,0 +1,579 @@
+use crate::{AtomicBlackBox, BlackBox};
+use core::cell::Cell;
+use core::ptr::NonNull;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::{fmt, mem};
+
+/// Epochs move in steps of 2, so the lowest bit of a participant's state is free
+/// to tell if it's pinned.
+const EPOCH_STEP: usize = 2;
+
+/// Set in a participant's state while it's pinned, next to the epoch it pinned.
+const PINNED: usize = 1;
+
+/// A participant's state while it's not pinned.
+const UNPINNED: usize = 0;
+
+/// Epoch-based reclamation for heap values which other threads may still be
+/// reading, e.g. the old configuration snapshot swapped out of an
+/// `AtomicBlackBox`.
+///
+/// - Readers `register()` a `Participant` once per thread, and `pin()` a `Guard`
+///   for as long as they read through shared pointers.
+/// - Writers unlink the old `BlackBox` and `retire()` it instead of dropping it.
+/// - A retired `BlackBox` is dropped once every guard which might have seen it is
+///   gone.
+///
+/// How: the collector keeps a global epoch, and every pinned participant records
+/// the epoch it pinned in. The global epoch only moves on when all the pinned
+/// participants have caught up with it, so a `BlackBox` retired in epoch `e` can't
+/// be seen by anybody once the global epoch is `e + 2`.
+///
+/// `EpochBlackBox` puts it all together, this is the same by hand with an
+/// `AtomicBlackBox`:
+///
+/// ```
+/// use raw_pointer_struct_in_rust::{AtomicBlackBox, BlackBox, Collector};
+/// use std::sync::atomic::Ordering;
+///
+/// let collector = Collector::new();
+/// let config = AtomicBlackBox::new(BlackBox::new("v1".to_owned()));
+///
+/// // Reader
+/// let participant = collector.register();
+/// let guard = participant.pin();
+/// // Safety: every writer retires the old heap value instead of dropping it.
+/// let v1 = unsafe { &*config.load(Ordering::SeqCst) };
+///
+/// // Writer
+/// let old = config.swap(BlackBox::new("v2".to_owned()));
+/// collector.register().pin().retire(old);
+///
+/// assert_eq!(v1, "v1");
+/// assert_eq!(collector.pending(), 1);
+/// drop(guard);
+/// collector.collect();
+/// assert_eq!(collector.pending(), 0);
+/// ```
+#[derive(Clone, Default)]
+pub struct Collector {
+    inner: Arc<CollectorInner>,
+}
+
+#[derive(Default)]
+struct CollectorInner {
+    epoch: AtomicUsize,
+    /// The state of every registered participant: `UNPINNED`, or the epoch it
+    /// pinned in with `PINNED` set.
+    participants: Mutex<Vec<Arc<AtomicUsize>>>,
+    /// The retired `BlackBox` which may still be in use.
+    garbage: Mutex<Vec<Retired>>,
+    /// The epoch `garbage` was last collected in.
+    collected_epoch: AtomicUsize,
+}
+
+/// A retired `BlackBox`, type-erased, dropped together with this.
+struct Retired {
+    /// The epoch it was retired in.
+    epoch: usize,
+    heap_value: NonNull<u8>,
+    drop_black_box: unsafe fn(NonNull<u8>),
+}
+
+/// Type-erased `drop::<BlackBox<T>>`.
+unsafe fn drop_black_box<T>(heap_value: NonNull<u8>) {
+    drop(BlackBox::from_raw(heap_value.cast::<T>().as_ptr()))
+}
+
+/// Every `Retired` comes from `retire()`, which needs `T: Send`.
+unsafe impl Send for Retired {}
+
+impl Drop for Retired {
+    fn drop(&mut self) {
+        // Safety: the heap value is retired once and nobody can see it any more.
+        unsafe { (self.drop_black_box)(self.heap_value) }
+    }
+}
+
+/// Nobody can leave the data in a broken state while holding the lock, so a
+/// panic in another thread is no reason to give up.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Collector {
+    /// Creating a collector with nothing registered and nothing retired.
+    pub fn new() -> Self {
+        Collector::default()
+    }
+
+    /// Registers a new participant, which is what a thread pins guards with. Keep
+    /// it around rather than registering for every read.
+    pub fn register(&self) -> Participant {
+        let state = Arc::new(AtomicUsize::new(UNPINNED));
+        lock(&self.inner.participants).push(state.clone());
+
+        Participant {
+            collector: self.clone(),
+            state,
+            pins: Cell::new(0),
+        }
+    }
+
+    /// Moves the epoch on (if every pinned participant allows it) and drops the
+    /// retired `BlackBox` nobody can see any more. `retire()` does this too (once
+    /// the epoch moved on), so it's only needed to free the leftovers once writing
+    /// stops.
+    pub fn collect(&self) {
+        // Two steps are enough for everything retired before the call, as long as
+        // nobody is pinned.
+        self.inner.try_advance();
+        self.inner.try_advance();
+
+        // Loaded under the lock, as another thread may have moved the epoch on and
+        // retired more in the meantime, which an older epoch would take for ancient.
+        let mut garbage = lock(&self.inner.garbage);
+        let epoch = self.inner.epoch.load(Ordering::SeqCst);
+        self.inner.collected_epoch.store(epoch, Ordering::Relaxed);
+        let (freeable, pending): (Vec<Retired>, Vec<Retired>) = mem::take(&mut *garbage)
+            .into_iter()
+            .partition(|retired| epoch.wrapping_sub(retired.epoch) >= 2 * EPOCH_STEP);
+        *garbage = pending;
+        drop(garbage);
+
+        // Outside the lock, as a destructor may retire more.
+        drop(freeable);
+    }
+
+    /// The number of retired `BlackBox` which are not dropped yet.
+    pub fn pending(&self) -> usize {
+        lock(&self.inner.garbage).len()
+    }
+
+    /// Returns `true` if both are the same collector.
+    pub fn ptr_eq(&self, other: &Collector) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    fn retire<T: Send + 'static>(&self, black_box: BlackBox<T>) {
+        if let Some(heap_value) = black_box.into_non_null() {
+            // The swap which unlinked the heap value is only `AcqRel`, the fence keeps
+            // the epoch load after it, so any reader pinned in a later epoch can't see
+            // the heap value.
+            atomic::fence(Ordering::SeqCst);
+            let epoch = self.inner.epoch.load(Ordering::SeqCst);
+            lock(&self.inner.garbage).push(Retired {
+                epoch,
+                heap_value: heap_value.cast(),
+                drop_black_box: drop_black_box::<T>,
+            });
+        }
+
+        // Nothing more can be freed until the epoch moves on, so a reader which
+        // stays pinned doesn't make every retire go through all the garbage again.
+        self.inner.try_advance();
+        let epoch = self.inner.epoch.load(Ordering::SeqCst);
+        if epoch != self.inner.collected_epoch.load(Ordering::Relaxed) {
+            self.collect();
+        }
+    }
+}
+
+impl CollectorInner {
+    /// Moves the global epoch on if every pinned participant pinned in the current
+    /// one.
+    fn try_advance(&self) {
+        let participants = lock(&self.participants);
+        let epoch = self.epoch.load(Ordering::SeqCst);
+
+        for state in participants.iter() {
+            let state = state.load(Ordering::SeqCst);
+            if state & PINNED != 0 && state & !PINNED != epoch {
+                return;
+            }
+        }
+
+        // Somebody else moving it on first is just as good.
+        let next = epoch.wrapping_add(EPOCH_STEP);
+        let _ = self
+            .epoch
+            .compare_exchange(epoch, next, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+impl fmt::Debug for Collector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Collector")
+            .field("epoch", &(self.inner.epoch.load(Ordering::Relaxed) / EPOCH_STEP))
+            .field("participants", &lock(&self.inner.participants).len())
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+/// A thread's registration with a `Collector`, created by `Collector::register()`.
+/// It can move to another thread, but can't be shared, as it pins for one thread
+/// at a time.
+pub struct Participant {
+    collector: Collector,
+    state: Arc<AtomicUsize>,
+    /// How many guards are alive, pinning nests.
+    pins: Cell<usize>,
+}
+
+impl Participant {
+    /// Pins the participant until the returned guard is dropped. No `BlackBox`
+    /// retired after this can be dropped while the guard is alive.
+    pub fn pin(&self) -> Guard<'_> {
+        if self.pins.get() == 0 {
+            let epoch = &self.collector.inner.epoch;
+
+            // If the epoch moved on while pinning, the collector may have missed the
+            // pin, so pin again in the new epoch.
+            loop {
+                let current = epoch.load(Ordering::SeqCst);
+                self.state.store(current | PINNED, Ordering::SeqCst);
+                if epoch.load(Ordering::SeqCst) == current {
+                    break;
+                }
+            }
+        }
+
+        self.pins.set(self.pins.get() + 1);
+        Guard { participant: self }
+    }
+
+    /// Returns `true` while a guard is alive.
+    pub fn is_pinned(&self) -> bool {
+        self.pins.get() != 0
+    }
+
+    /// The collector this participant is registered with.
+    pub fn collector(&self) -> &Collector {
+        &self.collector
+    }
+}
+
+impl fmt::Debug for Participant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Participant")
+            .field("pins", &self.pins.get())
+            .finish()
+    }
+}
+
+/// Unregisters, no guard can be alive by now, as they borrow the participant.
+impl Drop for Participant {
+    fn drop(&mut self) {
+        let mut participants = lock(&self.collector.inner.participants);
+        participants.retain(|state| !Arc::ptr_eq(state, &self.state));
+    }
+}
+
+/// Keeps its participant pinned, created by `Participant::pin()`.
+pub struct Guard<'participant> {
+    participant: &'participant Participant,
+}
+
+impl Guard<'_> {
+    /// Hands the unlinked `black_box` over to the collector, it's dropped once no
+    /// guard can see it any more. A **null pointer** `BlackBox` is just dropped.
+    ///
+    /// `T` must be `'static`, as the heap value may be dropped much later, and
+    /// `Send`, as it may be dropped by another thread.
+    pub fn retire<T: Send + 'static>(&self, black_box: BlackBox<T>) {
+        self.participant.collector.retire(black_box);
+    }
+
+    /// The collector this guard pins in.
+    pub fn collector(&self) -> &Collector {
+        &self.participant.collector
+    }
+}
+
+impl fmt::Debug for Guard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Guard")
+    }
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let pins = self.participant.pins.get() - 1;
+        self.participant.pins.set(pins);
+
+        if pins == 0 {
+            self.participant.state.store(UNPINNED, Ordering::SeqCst);
+        }
+    }
+}
+
+/// An `AtomicBlackBox` which always retires its old heap values with its
+/// `Collector`, so reading it under a `Guard` is safe:
+///
+/// ```
+/// use raw_pointer_struct_in_rust::{BlackBox, Collector, EpochBlackBox};
+///
+/// let collector = Collector::new();
+/// let config = EpochBlackBox::new(BlackBox::new("v1".to_owned()), &collector);
+/// let participant = collector.register();
+///
+/// let guard = participant.pin();
+/// let v1 = config.load(&guard).unwrap();
+/// config.store(BlackBox::new("v2".to_owned()));
+///
+/// // Still alive, the guard was pinned before `store()`.
+/// assert_eq!(v1, "v1");
+/// ```
+pub struct EpochBlackBox<T> {
+    atomic: AtomicBlackBox<T>,
+    collector: Collector,
+}
+
+impl<T> EpochBlackBox<T> {
+    /// Creating instance which takes over the heap value of `black_box`, and
+    /// retires the old heap values with `collector`.
+    pub fn new(black_box: BlackBox<T>, collector: &Collector) -> Self {
+        EpochBlackBox {
+            atomic: AtomicBlackBox::new(black_box),
+            collector: collector.clone(),
+        }
+    }
+
+    /// Returns the current heap value reference, or `None` for the **null pointer**
+    /// state. It stays valid as long as `guard`, even if the heap value is
+    /// replaced in the meantime.
+    ///
+    /// Panics if `guard` is from another collector.
+    pub fn load<'g>(&'g self, guard: &'g Guard<'_>) -> Option<&'g T> {
+        assert!(
+            self.collector.ptr_eq(guard.collector()),
+            "the guard is pinned in another collector"
+        );
+
+        // Safety: old heap values are retired, and the pinned guard keeps them alive.
+        unsafe { self.atomic.load(Ordering::SeqCst).as_ref() }
+    }
+
+    /// Returns the mutable heap value reference, `&mut self` proves nobody is
+    /// looking at it right now.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.atomic.get_mut()
+    }
+
+    /// Consumes the `EpochBlackBox` and returns its heap value.
+    pub fn into_inner(self) -> BlackBox<T> {
+        self.atomic.into_inner()
+    }
+
+    /// The collector old heap values are retired with.
+    pub fn collector(&self) -> &Collector {
+        &self.collector
+    }
+}
+
+impl<T: Send + 'static> EpochBlackBox<T> {
+    /// Replaces the heap value with the one of `new`, and retires the old one.
+    pub fn store(&self, new: BlackBox<T>) {
+        self.collector.retire(self.atomic.swap(new));
+    }
+
+    /// Takes the heap value out, leaving a **null pointer** behind, and retires it.
+    pub fn clear(&self) {
+        self.store(BlackBox::empty());
+    }
+}
+
+impl<T> fmt::Debug for EpochBlackBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EpochBlackBox")
+            .field("large_data_on_the_heap", &self.atomic.load(Ordering::Relaxed))
+            .field("collector", &self.collector)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    /// Counts its drops, and breaks its checksum right before it's freed, so a
+    /// reader which gets to it too late notices.
+    struct Snapshot {
+        id: usize,
+        checksum: usize,
+        dropped: Arc<AtomicUsize>,
+    }
+
+    impl Snapshot {
+        fn new(id: usize, dropped: &Arc<AtomicUsize>) -> BlackBox<Snapshot> {
+            BlackBox::new(Snapshot {
+                id,
+                checksum: !id,
+                dropped: dropped.clone(),
+            })
+        }
+
+        fn check(&self) {
+            assert_eq!(self.checksum, !self.id, "read a dropped snapshot");
+        }
+    }
+
+    impl Drop for Snapshot {
+        fn drop(&mut self) {
+            self.checksum = 0;
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn retired_box_outlives_the_guards_which_may_see_it() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let collector = Collector::new();
+        let snapshot = EpochBlackBox::new(Snapshot::new(1, &dropped), &collector);
+        let reader = collector.register();
+
+        let guard = reader.pin();
+        let first = snapshot.load(&guard).unwrap();
+
+        for id in 2..10 {
+            snapshot.store(Snapshot::new(id, &dropped));
+        }
+        collector.collect();
+
+        // Everything retired after the reader pinned is still there.
+        first.check();
+        assert_eq!(first.id, 1);
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        assert_eq!(collector.pending(), 8);
+
+        drop(guard);
+        collector.collect();
+        assert_eq!(dropped.load(Ordering::SeqCst), 8);
+        assert_eq!(collector.pending(), 0);
+
+        drop(snapshot);
+        assert_eq!(dropped.load(Ordering::SeqCst), 9);
+    }
+
+    #[test]
+    fn retire_collects_once_the_epoch_moves_on() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let collector = Collector::new();
+        let snapshot = EpochBlackBox::new(Snapshot::new(0, &dropped), &collector);
+        let reader = collector.register();
+
+        let guard = reader.pin();
+        for id in 1..10 {
+            snapshot.store(Snapshot::new(id, &dropped));
+        }
+        assert_eq!(collector.pending(), 9);
+
+        // No `collect()` by hand, the next store finds the epoch unblocked.
+        drop(guard);
+        snapshot.store(Snapshot::new(10, &dropped));
+        assert_eq!(collector.pending(), 0);
+        assert_eq!(dropped.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn pins_nest() {
+        let collector = Collector::new();
+        let participant = collector.register();
+
+        let outer = participant.pin();
+        let inner = participant.pin();
+        drop(outer);
+        assert!(participant.is_pinned());
+
+        inner.retire(BlackBox::new(1));
+        collector.collect();
+        assert_eq!(collector.pending(), 1);
+
+        drop(inner);
+        assert!(!participant.is_pinned());
+        collector.collect();
+        assert_eq!(collector.pending(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "another collector")]
+    fn guard_must_come_from_the_same_collector() {
+        let snapshot = EpochBlackBox::new(BlackBox::new(1), &Collector::new());
+        let participant = Collector::new().register();
+
+        snapshot.load(&participant.pin());
+    }
+
+    #[test]
+    fn retired_boxes_are_dropped_with_the_collector() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let collector = Collector::new();
+        let participant = collector.register();
+        let guard = participant.pin();
+
+        guard.retire(Snapshot::new(1, &dropped));
+        guard.retire(BlackBox::<Snapshot>::empty());
+        assert_eq!(collector.pending(), 1);
+
+        drop(guard);
+        drop((participant, collector));
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn stress_readers_never_see_a_dropped_snapshot() {
+        const READERS: usize = 8;
+        const WRITERS: usize = 2;
+        const STORES: usize = 2000;
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let collector = Collector::new();
+        let snapshot = EpochBlackBox::new(Snapshot::new(0, &dropped), &collector);
+        let done = AtomicBool::new(false);
+        let reads = AtomicUsize::new(0);
+
+        std::thread::scope(|scope| {
+            for _ in 0..READERS {
+                scope.spawn(|| {
+                    let participant = collector.register();
+                    while !done.load(Ordering::Relaxed) {
+                        let guard = participant.pin();
+                        let first = snapshot.load(&guard).unwrap();
+                        for _ in 0..10 {
+                            snapshot.load(&guard).unwrap().check();
+                        }
+                        first.check();
+                        reads.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+
+            let writers: Vec<_> = (0..WRITERS)
+                .map(|writer| {
+                    let (snapshot, dropped) = (&snapshot, &dropped);
+                    scope.spawn(move || {
+                        for store in 0..STORES {
+                            snapshot.store(Snapshot::new(writer * STORES + store + 1, dropped));
+                        }
+                    })
+                })
+                .collect();
+
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+
+        assert!(reads.load(Ordering::Relaxed) > 0);
+        collector.collect();
+        assert_eq!(collector.pending(), 0);
+        assert_eq!(dropped.load(Ordering::SeqCst), WRITERS * STORES);
+
+        drop(snapshot);
+        assert_eq!(dropped.load(Ordering::SeqCst), WRITERS * STORES + 1);
+    }
+}
@@ -0,0 +1,102 @@
+//! An opt-in observer for `BlackBox` dereference events, only compiled with the
+//! `deref-hook` cargo feature, so the normal build pays nothing for it.
+
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// What the registered hook gets told about every dereference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DerefEvent {
+    /// The heap address of the value being dereferenced.
+    pub address: usize,
+    /// The type name of the heap value, from `std::any::type_name`.
+    pub type_name: &'static str,
+    /// `true` for `DerefMut`, `false` for `Deref`.
+    pub mutable: bool,
+}
+
+/// The callback type which `set_deref_hook` accepts.
+pub type DerefHook = fn(&DerefEvent);
+
+/// The registered hook as a type-erased function pointer, null means no hook.
+static DEREF_HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers `hook` to be called on every `BlackBox` dereference, replacing the
+/// previous one. The hook is process wide and may be called from any thread.
+///
+/// ```
+/// use raw_pointer_struct_in_rust::{set_deref_hook, BlackBox, DerefEvent};
+///
+/// fn print_deref(event: &DerefEvent) {
+///     println!("[ dereference happens: {} at {:#x} ]", event.type_name, event.address);
+/// }
+///
+/// set_deref_hook(print_deref);
+/// assert_eq!(*BlackBox::new(1), 1);
+/// ```
+pub fn set_deref_hook(hook: DerefHook) {
+    DEREF_HOOK.store(hook as *mut (), Ordering::Release);
+}
+
+/// Unregisters the current hook, if any.
+pub fn clear_deref_hook() {
+    DEREF_HOOK.store(ptr::null_mut(), Ordering::Release);
+}
+
+/// Called by `Deref` and `DerefMut`, reports the event if a hook is registered.
+pub(crate) fn notify<T: ?Sized>(heap_value: *const T, mutable: bool) {
+    let hook = DEREF_HOOK.load(Ordering::Acquire);
+    if hook.is_null() {
+        return;
+    }
+
+    // Safety: the only non-null value ever stored is a `DerefHook` in `set_deref_hook`.
+    let hook: DerefHook = unsafe { core::mem::transmute::<*mut (), DerefHook>(hook) };
+    hook(&DerefEvent {
+        address: heap_value.cast::<()>() as usize,
+        type_name: std::any::type_name::<T>(),
+        mutable,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlackBox;
+    use std::sync::Mutex;
+
+    static EVENTS: Mutex<Vec<DerefEvent>> = Mutex::new(Vec::new());
+
+    fn record(event: &DerefEvent) {
+        EVENTS.lock().unwrap().push(*event);
+    }
+
+    #[test]
+    fn hook_reports_deref_events_with_address_and_type_name() {
+        let mut black_box = BlackBox::new(1u64);
+        let address = &*black_box as *const u64 as usize;
+
+        set_deref_hook(record);
+        let _ = *black_box;
+        *black_box += 1;
+        clear_deref_hook();
+        let _ = *black_box;
+
+        // Other tests deref their own boxes in parallel, only look at ours.
+        let events: Vec<DerefEvent> = EVENTS
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .filter(|event| event.address == address)
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                DerefEvent { address, type_name: "u64", mutable: false },
+                DerefEvent { address, type_name: "u64", mutable: true },
+            ]
+        );
+    }
+}
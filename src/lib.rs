@@ -1,8 +1,62 @@
 // #![allow(warnings)]
 
-use core::ptr::NonNull;
+use core::any::Any;
+use core::future::Future;
+#[cfg(not(feature = "allocator-api"))]
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::task::{Context, Poll};
+use std::alloc::Layout;
 use std::fmt;
 
+mod allocator;
+mod arena;
+mod atomic;
+mod by_address;
+mod epoch;
+#[cfg(feature = "deref-hook")]
+mod hook;
+mod pool;
+mod shared;
+mod thin;
+
+pub use allocator::AllocError;
+#[cfg(feature = "allocator-api")]
+pub use allocator::{Allocator, Global};
+#[cfg(not(feature = "allocator-api"))]
+use allocator::{Allocator, Global};
+pub use arena::{ArenaBlackBox, BlackBoxArena};
+pub use atomic::AtomicBlackBox;
+pub use by_address::ByAddress;
+pub use epoch::{Collector, EpochBlackBox, Guard, Participant};
+
+#[cfg(feature = "deref-hook")]
+pub use hook::{clear_deref_hook, set_deref_hook, DerefEvent, DerefHook};
+pub use pool::{BlackBoxPool, PoolStats, PooledBlackBox};
+pub use shared::{SharedBlackBox, WeakBlackBox};
+pub use thin::ThinBlackBox;
+
+/// The error returned when the heap value of a `BlackBox` is not available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BlackBoxError {
+    /// The `BlackBox` holds a **null pointer**, either it was created by
+    /// `BlackBox::empty()` or its heap value has been taken out.
+    NullPointer,
+}
+
+impl fmt::Display for BlackBoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlackBoxError::NullPointer => f.write_str("BlackBox holds a null pointer"),
+        }
+    }
+}
+
+impl std::error::Error for BlackBoxError {}
+
 /// A simple smart pointer structure which uses to hold a large data set on the 
 /// heap, and the total size of this structure should be just the size of the 
 /// raw pointer:
@@ -13,46 +67,641 @@ use std::fmt;
 /// We override the default `Deref` trait to just getting back the heap value reference
 /// rather the `BlackBox` instance itself.
 ///
-/// As we want to hold a raw pointer in this structure, and `Option<NonNull<T>>`
-/// describes either a **valid pointer** or a **null pointer**.  That's why we use
-/// `Option` here which only got 2 possible values:
+/// As we want to hold a raw pointer in this structure, and it's either a **valid
+/// pointer** or a **null pointer**. That's why we use `NonNull<T>` here, which
+/// only got 2 kinds of values:
+///
+/// - A heap address - means **valid pointer**
+/// - The last address (`usize::MAX`) - means **null pointer**
 ///
-/// - `Some()` - means **valid pointer**
-/// - `None` - means **null pointer**
+/// No heap value can start at the last address, a non zero-sized one wouldn't fit
+/// there, and zero-sized ones get their alignment as the (dangling) address.
 ///
 /// The **valid pointer** means:
 ///
 /// 1. Non null, it must point to particular `<T>` instance.
 /// 2. `<T>` instance should live on the **heap**.
-pub struct BlackBox<T: ?Sized> {
-    large_data_on_the_heap: Option<NonNull<T>>,
+///
+/// The heap memory comes from the global allocator, and goes back to it when the
+/// `BlackBox` is dropped. With the `allocator-api` feature, it comes from the `A`
+/// allocator instead (`Global` by default), see `new_in()`.
+///
+/// ## Layout
+///
+/// `#[repr(transparent)]` makes `BlackBox<T>` exactly that raw pointer, for every
+/// sized `T`. So it can be passed to (or returned from) C code by value, as a plain
+/// `*mut T` which is never null (`usize::MAX` for the **null pointer** state), or
+/// use `into_raw()` to hand over the ownership without the `BlackBox` type, which
+/// gives a real null raw pointer for the **null pointer** state.
+///
+/// With the `allocator-api` feature, `BlackBox` also stores its allocator, which
+/// a `#[repr(transparent)]` struct can't do, so it's `#[repr(C)]` instead: the raw
+/// pointer first, followed by the allocator. `Global` is zero-sized, so a
+/// `BlackBox<T>` is still one pointer wide, but only `as_mut_ptr()` or `into_raw()`
+/// can cross FFI then. A stateful allocator adds its own size.
+///
+/// As the real null pointer is never used, it's left for `None` (the niche
+/// optimisation), so `Option<BlackBox<T>>` is one word too.
+///
+/// ## Thread safety
+///
+/// `BlackBox` owns its heap value the same way `Box` does, so it's `Send` when `T`
+/// (and the allocator) is `Send`, and `Sync` when `T` (and the allocator) is `Sync`.
+/// A `BlackBox<Rc<_>>` still can't cross threads:
+///
+/// ```compile_fail
+/// use raw_pointer_struct_in_rust::BlackBox;
+/// use std::rc::Rc;
+///
+/// let black_box = BlackBox::new(Rc::new(1));
+/// std::thread::spawn(move || drop(black_box));
+/// ```
+///
+/// And a `BlackBox<Cell<_>>` can't be shared between threads:
+///
+/// ```compile_fail
+/// use raw_pointer_struct_in_rust::BlackBox;
+/// use std::cell::Cell;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// assert_sync(&BlackBox::new(Cell::new(1)));
+/// ```
+#[cfg_attr(not(feature = "allocator-api"), repr(transparent))]
+#[cfg_attr(feature = "allocator-api", repr(C))]
+pub struct BlackBox<T: ?Sized, A: Allocator = Global> {
+    large_data_on_the_heap: NonNull<T>,
+    #[cfg(feature = "allocator-api")]
+    alloc: A,
+    /// Without the `allocator-api` feature, `A` can only be the zero-sized `Global`,
+    /// nothing needs to be stored.
+    #[cfg(not(feature = "allocator-api"))]
+    alloc: PhantomData<A>,
 }
 
-///
-impl<T: fmt::Debug> BlackBox<T> {
+// The layout promise above, checked at compile time for a few very different `T`.
+const _: () = assert!(BlackBox::<u8>::is_pointer_sized());
+const _: () = assert!(BlackBox::<()>::is_pointer_sized());
+const _: () = assert!(BlackBox::<String>::is_pointer_sized());
+const _: () = assert!(BlackBox::<[u64; 1 << 20]>::is_pointer_sized());
+
+// The niche optimisation above, the same as `Option<ThinBlackBox<T>>`.
+const _: () = assert!(mem::size_of::<Option<BlackBox<String>>>() == mem::size_of::<usize>());
+const _: () = assert!(mem::size_of::<Option<ThinBlackBox<String>>>() == mem::size_of::<usize>());
+
+/// The address of the **null pointer** state, see above.
+const NULL_ADDRESS: usize = usize::MAX;
+
+/// The **null pointer** state of a sized `T`.
+const fn null_pointer<T>() -> NonNull<T> {
+    // Safety: `NULL_ADDRESS` is not 0.
+    unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(NULL_ADDRESS)) }
+}
+
+/// Layout helpers
+impl<T> BlackBox<T> {
+    /// Returns `true` if `BlackBox<T>` has the same size and alignment as a raw
+    /// pointer, which is always the case for a sized `T`. It's a `const fn`, so it
+    /// also works in a `const` assertion:
+    ///
+    /// ```
+    /// use raw_pointer_struct_in_rust::BlackBox;
+    ///
+    /// const _: () = assert!(BlackBox::<[u8; 4096]>::is_pointer_sized());
+    /// ```
+    pub const fn is_pointer_sized() -> bool {
+        mem::size_of::<Self>() == mem::size_of::<*mut T>()
+            && mem::align_of::<Self>() == mem::align_of::<*mut T>()
+    }
+
+    /// Evaluated once per `T` at compile time, by `new()` and `allocate_in()`, which
+    /// all the other constructors allocating a sized `T` go through.
+    const ASSERT_POINTER_SIZED: () = assert!(Self::is_pointer_sized());
+}
+
+/// Constructor
+impl<T> BlackBox<T> {
     /// Creating instance, and the `large_data_set`'s ownership will be moved into
     /// the created instance.
     pub fn new(large_data_set: T) -> Self {
+        let () = Self::ASSERT_POINTER_SIZED;
+
         // We box the original value here to MAKE SURE that value is allocated on the heap!!!
         let boxed_value = Box::new(large_data_set);
 
         // Convert `Box<T>` to `NonNull<T>` which is the raw pointer type
         let non_null = NonNull::from(Box::leak(boxed_value));
 
+        BlackBox::from_parts(non_null, Global)
+    }
+}
+
+/// Allocator related
+#[cfg(feature = "allocator-api")]
+impl<T, A: Allocator> BlackBox<T, A> {
+    /// Same with `new()`, but the heap memory comes from `alloc`.
+    pub fn new_in(large_data_set: T, alloc: A) -> Self {
+        BlackBox::new_uninit_in(alloc).write(large_data_set)
+    }
+
+    /// Same with `try_new()`, but the heap memory comes from `alloc`.
+    pub fn try_new_in(large_data_set: T, alloc: A) -> Result<Self, AllocError> {
+        Ok(BlackBox::try_new_uninit_in(alloc)?.write(large_data_set))
+    }
+
+    /// Same with `new_uninit()`, but the heap memory comes from `alloc`.
+    pub fn new_uninit_in(alloc: A) -> BlackBox<MaybeUninit<T>, A> {
+        match BlackBox::try_new_uninit_in(alloc) {
+            Ok(black_box) => black_box,
+            Err(error) => allocator::handle_alloc_error(error),
+        }
+    }
+
+    /// Same with `try_new_uninit()`, but the heap memory comes from `alloc`.
+    pub fn try_new_uninit_in(alloc: A) -> Result<BlackBox<MaybeUninit<T>, A>, AllocError> {
+        BlackBox::allocate_in(alloc, false)
+    }
+
+    /// Same with `new_zeroed()`, but the heap memory comes from `alloc`.
+    pub fn new_zeroed_in(alloc: A) -> BlackBox<MaybeUninit<T>, A> {
+        match BlackBox::allocate_in(alloc, true) {
+            Ok(black_box) => black_box,
+            Err(error) => allocator::handle_alloc_error(error),
+        }
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+impl<T: ?Sized, A: Allocator> BlackBox<T, A> {
+    /// Returns the allocator which the heap memory comes from.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+}
+
+impl<T: ?Sized, A: Allocator> BlackBox<T, A> {
+    /// The other half of `into_raw_parts()`.
+    #[cfg(feature = "allocator-api")]
+    const fn from_parts(large_data_on_the_heap: NonNull<T>, alloc: A) -> Self {
         BlackBox {
-            large_data_on_the_heap: Some(non_null),
+            large_data_on_the_heap,
+            alloc,
         }
     }
+
+    /// The other half of `into_raw_parts()`, `Global` has nothing to store.
+    #[cfg(not(feature = "allocator-api"))]
+    const fn from_parts(large_data_on_the_heap: NonNull<T>, alloc: A) -> Self {
+        mem::forget(alloc);
+        BlackBox {
+            large_data_on_the_heap,
+            alloc: PhantomData,
+        }
+    }
+
+    /// Splits into the raw pointer and the allocator, without running `Drop`.
+    fn into_raw_parts(self) -> (Option<NonNull<T>>, A) {
+        let this = mem::ManuallyDrop::new(self);
+
+        // Safety: `this` is never touched again, so the allocator is moved out once.
+        (this.non_null(), unsafe { ptr::read(this.alloc()) })
+    }
+
+    /// The raw pointer, `None` for the **null pointer** state.
+    fn non_null(&self) -> Option<NonNull<T>> {
+        if self.is_null() {
+            None
+        } else {
+            Some(self.large_data_on_the_heap)
+        }
+    }
+
+    /// The allocator which the heap memory comes from.
+    #[cfg(feature = "allocator-api")]
+    fn alloc(&self) -> &A {
+        &self.alloc
+    }
+
+    /// The allocator which the heap memory comes from.
+    #[cfg(not(feature = "allocator-api"))]
+    fn alloc(&self) -> &A {
+        const { assert!(mem::size_of::<A>() == 0) };
+
+        // Safety: without the feature, `Allocator` isn't public and `Global` is its
+        // only implementation, and a zero-sized value lives at any aligned non-null
+        // address.
+        unsafe { NonNull::<A>::dangling().as_ref() }
+    }
+}
+
+/// In-place construction, the large data set is created directly in its heap
+/// allocation rather than on the stack first
+impl<T> BlackBox<T> {
+    /// Creating instance with an uninitialised heap allocation for a `T`, write the
+    /// value through `as_mut_ptr()` (or `write()`) then call `assume_init()`.
+    pub fn new_uninit() -> BlackBox<MaybeUninit<T>> {
+        match BlackBox::allocate_in(Global, false) {
+            Ok(black_box) => black_box,
+            Err(error) => allocator::handle_alloc_error(error),
+        }
+    }
+
+    /// Same with `new_uninit()`, but the heap allocation is filled with `0` bytes.
+    /// That's a valid `T` for integers, floats, arrays and structs of those, then
+    /// `assume_init()` can be called right away.
+    pub fn new_zeroed() -> BlackBox<MaybeUninit<T>> {
+        match BlackBox::allocate_in(Global, true) {
+            Ok(black_box) => black_box,
+            Err(error) => allocator::handle_alloc_error(error),
+        }
+    }
+
+    /// Creating instance from the value returned by `init`, which is written into
+    /// the heap allocation made beforehand. That gives the optimiser the chance to
+    /// build the value in place, but it's not guaranteed, use `new_uninit()` or
+    /// `new_zeroed()` when the value must never touch the stack.
+    pub fn new_with(init: impl FnOnce() -> T) -> Self {
+        // If `init` panics, the uninitialised allocation is freed on the way out.
+        BlackBox::new_uninit().write(init())
+    }
+}
+
+/// Fallible allocation, reports the out of memory case as an `AllocError` rather
+/// than aborting the process like `BlackBox::new` does
+impl<T> BlackBox<T> {
+    /// Same with `new()`, but returns an `AllocError` if the heap allocation fails,
+    /// in which case `large_data_set` is dropped.
+    pub fn try_new(large_data_set: T) -> Result<Self, AllocError> {
+        Ok(BlackBox::try_new_uninit()?.write(large_data_set))
+    }
+
+    /// Same with `new_with()`, but returns an `AllocError` if the heap allocation
+    /// fails, in which case `init` is never called.
+    pub fn try_new_with(init: impl FnOnce() -> T) -> Result<Self, AllocError> {
+        Ok(BlackBox::try_new_uninit()?.write(init()))
+    }
+
+    /// Same with `new_uninit()`, but returns an `AllocError` if the heap allocation
+    /// fails.
+    pub fn try_new_uninit() -> Result<BlackBox<MaybeUninit<T>>, AllocError> {
+        BlackBox::allocate_in(Global, false)
+    }
+}
+
+impl<T, A: Allocator> BlackBox<MaybeUninit<T>, A> {
+    /// Allocates the (optionally zeroed) heap memory for a `T` from `alloc`.
+    fn allocate_in(alloc: A, zeroed: bool) -> Result<Self, AllocError> {
+        let () = BlackBox::<T>::ASSERT_POINTER_SIZED;
+
+        let non_null = allocator::allocate(&alloc, zeroed)?;
+        Ok(BlackBox::from_parts(non_null, alloc))
+    }
+
+    /// Converts to `BlackBox<T>`, the heap allocation stays where it is.
+    ///
+    /// # Safety
+    ///
+    /// Same with `MaybeUninit::assume_init`, the heap value must be a fully
+    /// initialised `T` by now.
+    pub unsafe fn assume_init(self) -> BlackBox<T, A> {
+        let (non_null, alloc) = self.into_raw_parts();
+        BlackBox::from_parts(non_null.map_or(null_pointer(), NonNull::cast), alloc)
+    }
+
+    /// Writes `value` into the heap allocation and converts to `BlackBox<T>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `BlackBox` holds a **null pointer**.
+    pub fn write(mut self, value: T) -> BlackBox<T, A> {
+        match self.get_mut() {
+            Some(uninit) => {
+                uninit.write(value);
+            }
+            None => panic!("{}", BlackBoxError::NullPointer),
+        }
+
+        // Safety: we just initialised it.
+        unsafe { self.assume_init() }
+    }
+}
+
+/// Moving the heap value in and out of the `BlackBox` without copying it
+impl<T: ?Sized> BlackBox<T> {
+    /// Consumes the `BlackBox` and returns the heap value as an ordinary `Box<T>`.
+    /// Only the ownership moves, the heap allocation itself stays where it is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `BlackBox` holds a **null pointer**.
+    pub fn into_box(self) -> Box<T> {
+        // `Drop` doesn't run for `self`, the `Box` takes over the heap value.
+        let (non_null, _) = self.into_raw_parts();
+        let non_null = non_null.expect("BlackBox::into_box called on a null pointer");
+
+        // Safety: the heap memory came from the `Global` allocator, which is the
+        // one `Box` uses, and `self` no longer owns it.
+        unsafe { Box::from_raw(non_null.as_ptr()) }
+    }
+}
+
+/// Raw pointer interop: handing the ownership of the heap value over to code which
+/// only deals with raw pointers (intrusive containers, C code) and taking it back.
+///
+/// `new()` allocates the heap value from the `Global` allocator with the layout of
+/// `T` (`Layout::for_value()` for an unsized `T`), exactly like `Box<T>`, so these
+/// raw pointers also go to and from `Box::into_raw()` and `Box::from_raw()`.
+/// Zero-sized heap values get a dangling pointer, which is never deallocated.
+impl<T> BlackBox<T> {
+    /// Consumes the `BlackBox` and returns the raw pointer to the heap value, or a
+    /// null raw pointer for the **null pointer** state. The heap value is not
+    /// dropped, turn it back into a `BlackBox` with `from_raw()` to free it.
+    pub fn into_raw(self) -> *mut T {
+        match self.into_non_null() {
+            Some(non_null) => non_null.as_ptr(),
+            None => ptr::null_mut(),
+        }
+    }
+}
+
+impl<T: ?Sized> BlackBox<T> {
+    /// Takes over the ownership of the heap value behind `raw`, a null raw pointer
+    /// gives the **null pointer** state.
+    ///
+    /// # Safety
+    ///
+    /// Unless it's null, `raw` must:
+    ///
+    /// - Come from `into_raw()`, `into_non_null()` or `leak()` of a `BlackBox<T>`,
+    ///   or from `Box::<T>::into_raw()`, so the heap memory belongs to the `Global`
+    ///   allocator with the layout of the heap value.
+    /// - Point to a valid `T`, which may have been updated in the meantime.
+    /// - Not be owned by anyone else: each pointer is taken back once, and no
+    ///   reference returned by `leak()` may be used afterwards.
+    pub unsafe fn from_raw(raw: *mut T) -> Self {
+        // Only the address changes for the null raw pointer, the metadata of an
+        // unsized `T` is kept.
+        let raw = if raw.is_null() { raw.with_addr(NULL_ADDRESS) } else { raw };
+        BlackBox::from_parts(NonNull::new_unchecked(raw), Global)
+    }
+
+    /// Same with `into_raw()`, but returns `None` for the **null pointer** state,
+    /// so it also works for an unsized `T`.
+    pub fn into_non_null(self) -> Option<NonNull<T>> {
+        self.into_raw_parts().0
+    }
+
+    /// Consumes the `BlackBox` and returns the heap value reference which lives as
+    /// long as needed, the heap value is never dropped (unless it's taken back with
+    /// `from_raw()`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `BlackBox` holds a **null pointer**.
+    pub fn leak<'a>(self) -> &'a mut T
+    where
+        T: 'a,
+    {
+        let non_null = self
+            .into_non_null()
+            .expect("BlackBox::leak called on a null pointer");
+
+        // Safety: nobody owns the heap value any more, and it's never freed.
+        unsafe { &mut *non_null.as_ptr() }
+    }
+}
+
+impl<T, A: Allocator> BlackBox<T, A> {
+    /// Consumes the `BlackBox` and moves the heap value back out of it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `BlackBox` holds a **null pointer**.
+    pub fn into_inner(mut self) -> T {
+        self.take().expect("BlackBox::into_inner called on a null pointer")
+    }
+}
+
+/// The **null pointer** state
+impl<T> BlackBox<T> {
+    /// Creating an instance which holds a **null pointer**, nothing is allocated
+    /// on the heap.
+    ///
+    /// The **null pointer** of an unsized `T` keeps the metadata (slice length,
+    /// vtable), which only a null raw pointer has, use `from_raw()` with one:
+    ///
+    /// ```
+    /// use raw_pointer_struct_in_rust::BlackBox;
+    /// use std::fmt::Display;
+    ///
+    /// let empty = unsafe { BlackBox::from_raw(std::ptr::null_mut::<i32>() as *mut dyn Display) };
+    /// assert!(empty.is_null());
+    /// ```
+    pub const fn empty() -> Self {
+        BlackBox::from_parts(null_pointer(), Global)
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+impl<T, A: Allocator> BlackBox<T, A> {
+    /// Same with `empty()`, later heap allocations (by `replace()` or `insert()`)
+    /// come from `alloc`.
+    pub const fn empty_in(alloc: A) -> Self {
+        BlackBox::from_parts(null_pointer(), alloc)
+    }
+}
+
+impl<T: ?Sized, A: Allocator> BlackBox<T, A> {
+    /// Returns `true` if the `BlackBox` holds a **null pointer**.
+    pub fn is_null(&self) -> bool {
+        self.large_data_on_the_heap.as_ptr().addr() == NULL_ADDRESS
+    }
+}
+
+impl<T, A: Allocator> BlackBox<T, A> {
+    /// Moves the heap value out and frees its heap memory, leaving a **null pointer**
+    /// behind. Returns `None` if the `BlackBox` already holds a **null pointer**.
+    pub fn take(&mut self) -> Option<T> {
+        let non_null = self.non_null()?;
+        self.large_data_on_the_heap = null_pointer();
+
+        // Safety: the heap value is moved out before its heap memory goes back to
+        // the allocator it came from, and `self` no longer owns either of them.
+        unsafe {
+            let value = non_null.as_ptr().read();
+            allocator::deallocate(self.alloc(), non_null.cast(), Layout::new::<T>());
+            Some(value)
+        }
+    }
+
+    /// Puts `value` into the `BlackBox` and returns the previous heap value, if any.
+    /// The existing heap allocation is reused, only a **null pointer** allocates.
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        match self.non_null() {
+            // Safety: we own the heap value, and `&mut self` makes sure nobody else
+            // is looking at it right now.
+            Some(non_null) => Some(std::mem::replace(
+                unsafe { &mut *non_null.as_ptr() },
+                value,
+            )),
+            None => {
+                let non_null: NonNull<T> = match allocator::allocate(self.alloc(), false) {
+                    Ok(non_null) => non_null,
+                    Err(error) => allocator::handle_alloc_error(error),
+                };
+
+                // Safety: the heap memory is freshly allocated for a `T`.
+                unsafe { non_null.as_ptr().write(value) };
+                self.large_data_on_the_heap = non_null;
+                None
+            }
+        }
+    }
+
+    /// Puts `value` into the `BlackBox`, dropping the previous heap value if any,
+    /// and returns a mutable reference to the new heap value.
+    pub fn insert(&mut self, value: T) -> &mut T {
+        drop(self.replace(value));
+
+        // Safety: `replace` always leaves a **valid pointer** behind.
+        unsafe { &mut *self.large_data_on_the_heap.as_ptr() }
+    }
+}
+
+/// Taking over an existing `Box<T>`, the value stays in the same heap allocation.
+impl<T: ?Sized> From<Box<T>> for BlackBox<T> {
+    fn from(boxed_value: Box<T>) -> Self {
+        BlackBox::from_parts(NonNull::from(Box::leak(boxed_value)), Global)
+    }
+}
+
+/// Unsized heap values: slices, `str` and trait objects
+impl<T: ?Sized> BlackBox<T> {
+    /// Converts `BlackBox<T>` into `BlackBox<U>` by handing the heap value to
+    /// `coerce` as a `Box<T>`, which is how a concrete type turns into a trait
+    /// object or an array turns into a slice on stable Rust:
+    ///
+    /// ```
+    /// use raw_pointer_struct_in_rust::BlackBox;
+    /// use std::fmt::Display;
+    ///
+    /// let black_box = BlackBox::new(1).unsize::<dyn Display>(|boxed| boxed);
+    /// assert_eq!(black_box.to_string(), "1");
+    /// ```
+    ///
+    /// The unsizing coercion only changes the pointer, the heap value stays in the
+    /// same heap allocation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `BlackBox` holds a **null pointer**, the metadata of `U` (e.g.
+    /// the vtable) only comes with a heap value. Use `from_raw()` with a null raw
+    /// pointer for a trait object in the **null pointer** state.
+    pub fn unsize<U: ?Sized>(self, coerce: impl FnOnce(Box<T>) -> Box<U>) -> BlackBox<U> {
+        if self.is_null() {
+            panic!("{}", BlackBoxError::NullPointer);
+        }
+
+        BlackBox::from(coerce(self.into_box()))
+    }
+}
+
+impl<T: Clone> BlackBox<[T]> {
+    /// Creating instance by cloning every element of `slice` into a new heap allocation.
+    pub fn from_slice(slice: &[T]) -> Self {
+        BlackBox::from(Box::<[T]>::from(slice))
+    }
+}
+
+impl<T> BlackBox<[T]> {
+    /// Creating instance from the elements of `vec`, the heap buffer is reused if
+    /// it has no spare capacity, otherwise it's shrunk to fit first.
+    pub fn from_vec(vec: Vec<T>) -> Self {
+        BlackBox::from(vec.into_boxed_slice())
+    }
 }
 
+impl<T> From<Vec<T>> for BlackBox<[T]> {
+    fn from(vec: Vec<T>) -> Self {
+        BlackBox::from_vec(vec)
+    }
+}
+
+impl<T: Clone> From<&[T]> for BlackBox<[T]> {
+    fn from(slice: &[T]) -> Self {
+        BlackBox::from_slice(slice)
+    }
+}
+
+impl From<String> for BlackBox<str> {
+    fn from(string: String) -> Self {
+        BlackBox::from(string.into_boxed_str())
+    }
+}
+
+impl From<&str> for BlackBox<str> {
+    fn from(string: &str) -> Self {
+        BlackBox::from(Box::<str>::from(string))
+    }
+}
+
+impl std::str::FromStr for BlackBox<str> {
+    type Err = std::convert::Infallible;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(BlackBox::from(string))
+    }
+}
+
+/// Downcasting a type-erased heap value back to its concrete type, the same for
+/// `dyn Any`, `dyn Any + Send` and `dyn Any + Send + Sync`.
+macro_rules! impl_downcast {
+    ($($any:ty),+) => {$(
+        impl<A: Allocator> BlackBox<$any, A> {
+            /// Returns `true` if the heap value is a `T`, `false` for the **null
+            /// pointer**.
+            pub fn is<T: Any>(&self) -> bool {
+                self.get().is_some_and(|value| value.is::<T>())
+            }
+
+            /// Converts into a `BlackBox<T>` if the heap value is a `T`, otherwise
+            /// hands the `BlackBox` back. Only the pointer changes, the heap value
+            /// stays in the same heap allocation.
+            pub fn downcast<T: Any>(self) -> Result<BlackBox<T, A>, Self> {
+                if !self.is::<T>() {
+                    return Err(self);
+                }
+
+                // Just checked that the heap value is a `T`.
+                let (large_data_on_the_heap, alloc) = self.into_raw_parts();
+                Ok(BlackBox::from_parts(
+                    large_data_on_the_heap.map_or(null_pointer(), NonNull::cast),
+                    alloc,
+                ))
+            }
+
+            /// Returns the heap value reference if it's a `T`.
+            pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+                self.get()?.downcast_ref()
+            }
+
+            /// Returns the mutable heap value reference if it's a `T`.
+            pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+                self.get_mut()?.downcast_mut()
+            }
+        }
+    )+};
+}
+
+impl_downcast!(dyn Any, dyn Any + Send, dyn Any + Send + Sync);
+
 /// We want `{:?}` or `{:#?}` work for `BlackBox` instance, that's why we ask for
 /// the `T` should implement the `fmt::Debug` trait
-impl<T: fmt::Debug> fmt::Debug for BlackBox<T> {
+impl<T: ?Sized + fmt::Debug, A: Allocator> fmt::Debug for BlackBox<T, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Just for printing the data (not move the ownership), that's why
         // return `&T` here rather the `T`. Wrap the result into `Option`
         // can deal with the no value case.
-        let data_option_ref: Option<&T> = match self.large_data_on_the_heap {
+        let data_option_ref: Option<&T> = match self.non_null() {
             Some(data) => {
                 // Get back raw pointer (point to) `T` from `NonNull<T>`, and keep
                 // in mind that the `T` actually is a `Box<T>` here!!!
@@ -73,30 +722,397 @@ impl<T: fmt::Debug> fmt::Debug for BlackBox<T> {
     }
 }
 
+/// Debugging the heap allocation rather than the heap value
+impl<T: ?Sized, A: Allocator> BlackBox<T, A> {
+    /// Returns a view whose `Debug` works for any `T`, even one which doesn't
+    /// implement `fmt::Debug`, or unsized ones like `dyn Trait`:
+    ///
+    /// - `{:?}` prints the type name and heap address: `BlackBox<u32>(0x5581a0)`.
+    /// - `{:#?}` prints the heap address, `size_of_val`, alignment and whether it
+    ///   holds a **null pointer**.
+    ///
+    /// ```
+    /// use raw_pointer_struct_in_rust::BlackBox;
+    ///
+    /// struct NoDebug([u8; 64]);
+    ///
+    /// let black_box = BlackBox::new(NoDebug([0; 64]));
+    /// assert!(format!("{:?}", black_box.debug_layout()).starts_with("BlackBox<"));
+    /// assert!(format!("{:#?}", black_box.debug_layout()).contains("size: 64"));
+    /// ```
+    pub fn debug_layout(&self) -> DebugLayout<'_, T, A> {
+        DebugLayout { black_box: self }
+    }
+}
+
+/// The view returned by `BlackBox::debug_layout()`.
+pub struct DebugLayout<'a, T: ?Sized, A: Allocator = Global> {
+    black_box: &'a BlackBox<T, A>,
+}
+
+impl<T: ?Sized, A: Allocator> fmt::Debug for DebugLayout<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let type_name = std::any::type_name::<T>();
+
+        if !f.alternate() {
+            return write!(f, "BlackBox<{}>({:p})", type_name, self.black_box.address());
+        }
+
+        let mut debug = f.debug_struct("BlackBox");
+        debug
+            .field("type", &format_args!("{}", type_name))
+            .field("address", &format_args!("{:p}", self.black_box.address()))
+            .field("is_null", &self.black_box.is_null());
+
+        // Unsized heap values only know their size and alignment through the value.
+        if let Some(value) = self.black_box.get() {
+            debug
+                .field("size", &mem::size_of_val(value))
+                .field("align", &mem::align_of_val(value));
+        }
+
+        debug.finish()
+    }
+}
+
+/// Fallible access to the heap value
+impl<T: ?Sized, A: Allocator> BlackBox<T, A> {
+    /// Returns the heap value reference, or `None` if the `BlackBox` holds a
+    /// **null pointer**.
+    pub fn get(&self) -> Option<&T> {
+        // Safety: a **valid pointer** points to the heap value we own, and the
+        // returned reference borrows `self`, so the value can't be freed under it.
+        self.non_null()
+            .map(|non_null| unsafe { &*non_null.as_ptr() })
+    }
+
+    /// Same with `get()`, but reports the **null pointer** as a `BlackBoxError`.
+    pub fn try_get(&self) -> Result<&T, BlackBoxError> {
+        self.get().ok_or(BlackBoxError::NullPointer)
+    }
+
+    /// Returns the mutable heap value reference, or `None` if the `BlackBox` holds
+    /// a **null pointer**.
+    ///
+    /// As `BlackBox` is the only owner of the heap value, `&mut self` is enough to
+    /// prove nobody else is reading or writing it, the same rule as `Box<T>`.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // Safety: the returned reference borrows `self` mutably, so no other
+        // reference to the heap value can exist while it's alive.
+        self.non_null()
+            .map(|non_null| unsafe { &mut *non_null.as_ptr() })
+    }
+}
+
+impl<T, A: Allocator> BlackBox<T, A> {
+    /// Returns the raw pointer to the heap value, or a null raw pointer if the
+    /// `BlackBox` holds a **null pointer**. No reference is created along the way.
+    ///
+    /// The `BlackBox` still owns the heap value, so the raw pointer:
+    ///
+    /// - Is only valid until the `BlackBox` is dropped, or its heap value is taken
+    ///   out or replaced by a new allocation.
+    /// - Must not be used to read or write while a reference returned by `get()`,
+    ///   `get_mut()` or a dereference is still alive, the reference is the one in
+    ///   charge at that time. Once the reference is gone, the raw pointer can be
+    ///   used again.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        match self.non_null() {
+            Some(non_null) => non_null.as_ptr(),
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    /// Same with `as_mut_ptr()`, but `&self` is enough as the raw pointer is only
+    /// for reading. Writing through it needs `as_mut_ptr()`.
+    pub fn as_ptr(&self) -> *const T {
+        match self.non_null() {
+            Some(non_null) => non_null.as_ptr(),
+            None => std::ptr::null(),
+        }
+    }
+}
+
 /// Override the default `deref` trait to get back the heap value reference rather 
 /// than the structure instance itself, make it looks more natural and transparent.
-impl<T> std::ops::Deref for BlackBox<T> {
+impl<T: ?Sized, A: Allocator> std::ops::Deref for BlackBox<T, A> {
     type Target = T;
 
-    fn deref(&self) -> &Self::Target {
-        println!("[ dereference happens >>>>>>>>>>>>>>>>>>>>> ]\n");
+    fn deref(&self) -> &Self::Target {
+        // Here, we return the heap value reference rather than `&self`. A **null
+        // pointer** has nothing to return, use `get()` or `try_get()` to handle
+        // that case without panicking.
+        let value = match self.try_get() {
+            Ok(value) => value,
+            Err(error) => panic!("{}", error),
+        };
+
+        #[cfg(feature = "deref-hook")]
+        hook::notify(value, false);
+
+        value
+    }
+}
+
+/// The mutable version of the dereference above, so the heap value can be updated
+/// in place rather than cloning it out and creating a new `BlackBox`.
+impl<T: ?Sized, A: Allocator> std::ops::DerefMut for BlackBox<T, A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let non_null = match self.non_null() {
+            Some(non_null) => non_null,
+            None => panic!("{}", BlackBoxError::NullPointer),
+        };
+
+        #[cfg(feature = "deref-hook")]
+        hook::notify(non_null.as_ptr(), true);
+
+        // Safety: see `get_mut()`.
+        unsafe { &mut *non_null.as_ptr() }
+    }
+}
+
+/// Deep copy: the cloned `BlackBox` gets its own heap allocation (from a clone of
+/// the allocator) holding a clone of the heap value, and a **null pointer** clones
+/// to a **null pointer**.
+impl<T: Clone, A: Allocator + Clone> Clone for BlackBox<T, A> {
+    fn clone(&self) -> Self {
+        let alloc = self.alloc().clone();
+        match self.get() {
+            Some(value) => match BlackBox::allocate_in(alloc, false) {
+                Ok(black_box) => black_box.write(value.clone()),
+                Err(error) => allocator::handle_alloc_error(error),
+            },
+            None => BlackBox::from_parts(null_pointer(), alloc),
+        }
+    }
+
+    /// Reuses the existing heap allocation (and `T::clone_from`) when both sides
+    /// hold a **valid pointer**, so nothing is allocated.
+    fn clone_from(&mut self, source: &Self) {
+        match (self.get_mut(), source.get()) {
+            (Some(value), Some(source_value)) => value.clone_from(source_value),
+            (None, Some(source_value)) => drop(self.replace(source_value.clone())),
+            (_, None) => drop(self.take()),
+        }
+    }
+}
+
+/// Identity rather than value comparison
+impl<T: ?Sized, A: Allocator> BlackBox<T, A> {
+    /// Returns `true` if both point to the same heap address, two **null pointer**
+    /// are equal too. Like `Box`, zero-sized heap values all share one dangling
+    /// address.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+
+    /// The heap address (without the metadata of an unsized `T`), null for the
+    /// **null pointer** state.
+    fn address(&self) -> *const u8 {
+        match self.non_null() {
+            Some(non_null) => non_null.cast::<u8>().as_ptr(),
+            None => ptr::null(),
+        }
+    }
+}
+
+/// The comparison traits look through the pointer and compare the heap values,
+/// the same as `Box<T>`. A **null pointer** compares like `None`: equal to another
+/// **null pointer**, and less than any heap value.
+impl<T: ?Sized + PartialEq, A: Allocator> PartialEq for BlackBox<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<T: ?Sized + Eq, A: Allocator> Eq for BlackBox<T, A> {}
+
+impl<T: ?Sized + PartialOrd, A: Allocator> PartialOrd for BlackBox<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.get().partial_cmp(&other.get())
+    }
+}
+
+impl<T: ?Sized + Ord, A: Allocator> Ord for BlackBox<T, A> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.get().cmp(&other.get())
+    }
+}
+
+/// Hashes the heap value exactly like `T` does, so a `BlackBox<T>` hashes the same
+/// as the `T` it holds. A **null pointer** hashes nothing.
+impl<T: ?Sized + std::hash::Hash, A: Allocator> std::hash::Hash for BlackBox<T, A> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        if let Some(value) = self.get() {
+            value.hash(state);
+        }
+    }
+}
+
+/// Prints the heap value, the same as `Box<T>`. Like a dereference, it panics on
+/// the **null pointer**, `{:?}` handles that case.
+impl<T: ?Sized + fmt::Display, A: Allocator> fmt::Display for BlackBox<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// `{:p}` prints the heap address, `0x0` for the **null pointer**.
+impl<T: ?Sized, A: Allocator> fmt::Pointer for BlackBox<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Pointer::fmt(&self.address(), f)
+    }
+}
+
+/// The conversion traits below go through the dereference, so they panic on the
+/// **null pointer** as well.
+impl<T: ?Sized, A: Allocator> AsRef<T> for BlackBox<T, A> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized, A: Allocator> AsMut<T> for BlackBox<T, A> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+/// `Eq`, `Ord` and `Hash` above behave the same as `T`'s, which is what `Borrow`
+/// asks for, so a `HashMap<BlackBox<T>, _>` can be looked up by `&T`.
+///
+/// Like `AsRef`, these `Borrow` impls (also the `str` and `[T]` ones below) panic
+/// on the **null pointer**, so null keys are not supported in a map which is
+/// looked up that way. They still go in, as `Eq`, `Ord` and `Hash` handle the
+/// **null pointer**, but a later lookup by `&T` panics as soon as it compares
+/// against one, e.g. a `BTreeMap` always does, a `HashMap` does on a hash match.
+/// Looking up by `&BlackBox<T>` is fine.
+impl<T: ?Sized, A: Allocator> std::borrow::Borrow<T> for BlackBox<T, A> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized, A: Allocator> std::borrow::BorrowMut<T> for BlackBox<T, A> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+/// `String` borrows as `str`, so a `HashMap<BlackBox<String>, _>` can be looked
+/// up by `&str` too.
+impl<A: Allocator> std::borrow::Borrow<str> for BlackBox<String, A> {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+/// And a `HashMap<BlackBox<Vec<T>>, _>` by `&[T]`.
+impl<T, A: Allocator> std::borrow::Borrow<[T]> for BlackBox<Vec<T>, A> {
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+/// Same with `BlackBox::new()`.
+impl<T> From<T> for BlackBox<T> {
+    fn from(large_data_set: T) -> Self {
+        BlackBox::new(large_data_set)
+    }
+}
+
+/// A heap allocated `T::default()`, not the **null pointer**, which is what
+/// `BlackBox::empty()` is for.
+impl<T: Default> Default for BlackBox<T> {
+    fn default() -> Self {
+        BlackBox::new(T::default())
+    }
+}
+
+/// Pinning: the heap value never moves while the `BlackBox` itself is moved
+/// around, so a `Pin<BlackBox<T>>` can hold self-referential data and `!Unpin`
+/// futures, the same as `Pin<Box<T>>`.
+impl<T> BlackBox<T> {
+    /// Creating instance, and pinning the heap value right away.
+    pub fn pin(large_data_set: T) -> Pin<Self> {
+        BlackBox::new(large_data_set).into_pin()
+    }
+}
+
+impl<T: ?Sized, A: Allocator + 'static> BlackBox<T, A> {
+    /// Pins the heap value, it won't move again until it's dropped.
+    ///
+    /// The allocator has to be `'static` like `Box::into_pin()`: a borrowed one
+    /// (e.g. `&BlackBoxArena`) could reuse the memory after a `mem::forget()`,
+    /// without the heap value ever being dropped.
+    #[cfg_attr(
+        feature = "allocator-api",
+        doc = r#"
+```compile_fail
+use raw_pointer_struct_in_rust::{BlackBox, BlackBoxArena};
+
+let arena = BlackBoxArena::new();
+let pinned = BlackBox::new_in(1, &arena).into_pin();
+```"#
+    )]
+    pub fn into_pin(self) -> Pin<Self> {
+        // Safety: nothing can move the heap value out of a `Pin<BlackBox<T>>`. The
+        // `Pin` only hands out `&T` and `Pin<&mut T>`, and `take()`, `replace()` or
+        // `into_inner()` all need the `BlackBox` itself, which `Pin` keeps hidden
+        // unless `T: Unpin`. And a `'static` allocator can't be dropped or reset to
+        // reuse the memory before the heap value is dropped.
+        unsafe { Pin::new_unchecked(self) }
+    }
+}
+
+impl<T: ?Sized, A: Allocator + 'static> From<BlackBox<T, A>> for Pin<BlackBox<T, A>> {
+    fn from(black_box: BlackBox<T, A>) -> Self {
+        black_box.into_pin()
+    }
+}
+
+/// Moving a `BlackBox` only moves the raw pointer, never the heap value, so the
+/// `BlackBox` itself is always `Unpin`, whatever `T` is.
+impl<T: ?Sized, A: Allocator> Unpin for BlackBox<T, A> {}
+
+/// Polls the heap future in place. `!Unpin` futures need pinning first, then
+/// `Pin<BlackBox<F>>` is the future, e.g. `BlackBox::pin(async { .. })`.
+impl<F: ?Sized + Future + Unpin, A: Allocator> Future for BlackBox<F, A> {
+    type Output = F::Output;
 
-        // Here, we return `self.large_data_on_the_heap` reference rather than
-        // return `&self`. As that's a raw pointer to `Box<T>`, then we need to
-        // `take it out`.
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        F::poll(Pin::new(&mut **self), cx)
+    }
+}
 
-        // `self.large_data_on_the_heap.as_ref()` returns `Option<&NonNull<T>>`,
-        // `unwrap()` that return back `&NonNull<T>`. And `T` actually is a `Box<T>`!!!
-        let option_ref: &NonNull<T> = self.large_data_on_the_heap.as_ref().unwrap();
+/// `NonNull<T>` is neither `Send` nor `Sync`, as the compiler can't tell whether
+/// the pointer is shared. `BlackBox` is the only owner of the heap value, so it's
+/// as thread safe as `T` itself (and its allocator), the same rule as `Box<T>`.
+unsafe impl<T: ?Sized + Send, A: Allocator + Send> Send for BlackBox<T, A> {}
+unsafe impl<T: ?Sized + Sync, A: Allocator + Sync> Sync for BlackBox<T, A> {}
 
-        let raw_pointer = option_ref.as_ptr();
-        unsafe { &*raw_pointer }
+/// `BlackBox` only holds a raw pointer, so nobody frees that heap memory unless we
+/// do it here: run `T`'s destructor in place, then give the heap memory back to the
+/// allocator it came from, exactly once.
+impl<T: ?Sized, A: Allocator> Drop for BlackBox<T, A> {
+    fn drop(&mut self) {
+        // A `None` here means there is nothing on the heap to free.
+        if let Some(non_null) = self.non_null() {
+            // Safety: the heap memory came from `self.alloc()`, and we are the only
+            // owner of it.
+            unsafe {
+                let layout = Layout::for_value(non_null.as_ref());
+                ptr::drop_in_place(non_null.as_ptr());
+                allocator::deallocate(self.alloc(), non_null.cast(), layout);
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
     use std::mem;
 
     #[test]
@@ -121,10 +1137,10 @@ mod tests {
             // string content itself (22 bytes), so that's cheap copy:)
             string_box = BlackBox::new(large_data_string_value);
 
-            // This will cause `dereference`, that's why will get back a `String` value!!!
-            // As the `clone()` only needs to copy the raw pointer size, so that's a cheap copy as
-            // well.
-            let temp_value: String = string_box.clone();
+            // Dereference first, that's why will get back a `String` value rather than
+            // another `BlackBox<String>`!!! `string_box.clone()` would deep copy the whole
+            // heap value into a new `BlackBox<String>` instead.
+            let temp_value: String = (*string_box).clone();
 
             // Should be the same size with `BlackBox<T>` (only the raw pointer size)
             println!("string_box size: {}\n", mem::size_of_val(&string_box));
@@ -139,13 +1155,14 @@ mod tests {
         // still available, u still can print the `string_box` with the original string content.
         println!("string_box: {:#?}\n", &string_box);
 
-        // Cheap copy and dereference happens again
-        let temp_value: String = string_box.clone();
+        // Dereference happens again
+        let temp_value: String = (*string_box).clone();
         println!("temp_value: {}\n", &temp_value);
     }
 
     #[test]
     fn heap_allocated_struct_box() {
+        #[allow(dead_code)]
         #[derive(Debug, Clone)]
         struct Address {
             country: String,
@@ -153,6 +1170,7 @@ mod tests {
             street: String,
         }
 
+        #[allow(dead_code)]
         #[derive(Debug, Clone)]
         struct Person {
             first_name: String,
@@ -177,8 +1195,8 @@ mod tests {
 
         let struct_box: BlackBox<Person> = BlackBox::new(person);
 
-        // It should cause dereference `BlackBox` instance and get back the `Person` instance
-        let temp_person_struct_value: Person = struct_box.clone();
+        // Dereference `BlackBox` instance first and get back the `Person` instance
+        let temp_person_struct_value: Person = (*struct_box).clone();
 
         // Should be the same size with `BlackBox<T>` (only the raw pointer size)
         println!("struct_box size: {} bytes\n", mem::size_of_val(&struct_box));
@@ -190,4 +1208,741 @@ mod tests {
             mem::size_of_val(&temp_person_struct_value)
         );
     }
+
+    /// Counts how many times its destructor runs, so the tests can prove the heap
+    /// value is dropped exactly once.
+    #[derive(Debug)]
+    struct DropCounter<'a> {
+        counter: &'a Cell<usize>,
+    }
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.counter.set(self.counter.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drop_frees_the_heap_value_exactly_once() {
+        let counter = Cell::new(0);
+
+        {
+            let black_box = BlackBox::new(DropCounter { counter: &counter });
+            assert_eq!(black_box.counter.get(), 0);
+        }
+
+        assert_eq!(counter.get(), 1);
+    }
+
+    #[test]
+    fn drop_handles_the_null_pointer_state() {
+        let counter = Cell::new(0);
+        let mut black_box = BlackBox::new(DropCounter { counter: &counter });
+
+        // Free the heap value by hand and leave the null pointer behind, `Drop`
+        // should have nothing left to do.
+        let raw = black_box.as_mut_ptr();
+        black_box.large_data_on_the_heap = null_pointer();
+        unsafe { drop(Box::from_raw(raw)) };
+        assert_eq!(counter.get(), 1);
+
+        drop(black_box);
+        assert_eq!(counter.get(), 1);
+    }
+
+    #[test]
+    fn round_trip_through_box_keeps_the_same_heap_allocation() {
+        let boxed_value = Box::new("Very large string data".to_owned());
+        let heap_address: *const String = &*boxed_value;
+
+        let black_box: BlackBox<String> = BlackBox::from(boxed_value);
+        assert_eq!(&*black_box as *const String, heap_address);
+
+        let boxed_value = black_box.into_box();
+        assert_eq!(&*boxed_value as *const String, heap_address);
+        assert_eq!(*boxed_value, "Very large string data");
+    }
+
+    #[test]
+    fn into_inner_moves_the_value_out_without_dropping_it() {
+        let counter = Cell::new(0);
+        let black_box = BlackBox::new(DropCounter { counter: &counter });
+
+        let value = black_box.into_inner();
+        assert_eq!(counter.get(), 0);
+
+        drop(value);
+        assert_eq!(counter.get(), 1);
+    }
+
+    #[test]
+    fn empty_box_holds_a_null_pointer() {
+        let black_box: BlackBox<String> = BlackBox::empty();
+        assert!(black_box.is_null());
+        assert!(!BlackBox::new(1).is_null());
+
+        let raw = ptr::slice_from_raw_parts_mut(ptr::null_mut::<u8>(), 0) as *mut str;
+        let black_box: BlackBox<str> = unsafe { BlackBox::from_raw(raw) };
+        assert!(black_box.is_null());
+        assert_eq!(black_box.get(), None);
+    }
+
+    #[test]
+    fn option_uses_the_null_pointer_niche() {
+        let mut slot = Some(BlackBox::new(1));
+        assert_eq!(mem::size_of_val(&slot), mem::size_of::<usize>());
+        assert_eq!(slot.as_deref(), Some(&1));
+
+        // The **null pointer** state is still a `Some`.
+        slot = Some(BlackBox::empty());
+        assert!(slot.as_ref().unwrap().is_null());
+        assert!(slot.take().unwrap().into_raw().is_null());
+        assert!(slot.is_none());
+    }
+
+    #[test]
+    fn take_leaves_a_null_pointer_behind() {
+        let mut black_box = BlackBox::new("Very large string data".to_owned());
+
+        assert_eq!(black_box.take().as_deref(), Some("Very large string data"));
+        assert!(black_box.is_null());
+        assert_eq!(black_box.take(), None);
+    }
+
+    #[test]
+    fn replace_reuses_the_existing_heap_allocation() {
+        let mut black_box: BlackBox<i32> = BlackBox::empty();
+        assert_eq!(black_box.replace(1), None);
+
+        let heap_address: *const i32 = &*black_box;
+        assert_eq!(black_box.replace(2), Some(1));
+        assert_eq!(&*black_box as *const i32, heap_address);
+        assert_eq!(*black_box, 2);
+    }
+
+    #[test]
+    fn insert_drops_the_previous_heap_value() {
+        let counter = Cell::new(0);
+        let mut black_box: BlackBox<DropCounter> = BlackBox::empty();
+
+        black_box.insert(DropCounter { counter: &counter });
+        assert_eq!(counter.get(), 0);
+
+        black_box.insert(DropCounter { counter: &counter });
+        assert_eq!(counter.get(), 1);
+
+        drop(black_box);
+        assert_eq!(counter.get(), 2);
+    }
+
+    #[test]
+    fn get_and_try_get_report_the_null_pointer() {
+        let mut black_box = BlackBox::new(1);
+        assert_eq!(black_box.get(), Some(&1));
+        assert_eq!(black_box.try_get(), Ok(&1));
+
+        black_box.take();
+        assert_eq!(black_box.get(), None);
+        assert_eq!(black_box.try_get(), Err(BlackBoxError::NullPointer));
+        assert_eq!(
+            BlackBoxError::NullPointer.to_string(),
+            "BlackBox holds a null pointer"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "BlackBox holds a null pointer")]
+    fn deref_panics_on_the_null_pointer() {
+        let black_box: BlackBox<i32> = BlackBox::empty();
+        let _ = *black_box;
+    }
+
+    #[test]
+    fn deref_mut_updates_the_heap_value_in_place() {
+        let mut black_box = BlackBox::new("Very large".to_owned());
+        let heap_address: *const String = &*black_box;
+
+        black_box.push_str(" string data");
+        if let Some(value) = black_box.get_mut() {
+            value.push('!');
+        }
+
+        assert_eq!(&*black_box as *const String, heap_address);
+        assert_eq!(*black_box, "Very large string data!");
+
+        black_box.take();
+        assert!(black_box.get_mut().is_none());
+        assert!(black_box.as_mut_ptr().is_null());
+    }
+
+    /// Interleaves raw pointer and reference access the way `as_mut_ptr()` documents
+    /// it, run it with `cargo +nightly miri test` to check the aliasing rules.
+    #[test]
+    fn raw_pointer_and_references_take_turns() {
+        let mut black_box = BlackBox::new(1);
+        let raw_pointer = black_box.as_mut_ptr();
+
+        unsafe { *raw_pointer += 1 };
+        *black_box += 1;
+        assert_eq!(*black_box.get().unwrap(), 3);
+
+        // The references above are gone, the raw pointer is usable again.
+        unsafe { *raw_pointer += 1 };
+        assert_eq!(unsafe { *raw_pointer }, 4);
+        *black_box.get_mut().unwrap() += 1;
+        assert_eq!(black_box.into_inner(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "BlackBox holds a null pointer")]
+    fn deref_mut_panics_on_the_null_pointer() {
+        let mut black_box: BlackBox<i32> = BlackBox::empty();
+        *black_box = 1;
+    }
+
+    #[test]
+    fn clone_deep_copies_into_a_new_heap_allocation() {
+        let black_box = BlackBox::new("Very large string data".to_owned());
+        let cloned: BlackBox<String> = black_box.clone();
+
+        assert_eq!(*cloned, *black_box);
+        assert_ne!(&*cloned as *const String, &*black_box as *const String);
+
+        let empty: BlackBox<String> = BlackBox::empty();
+        assert!(empty.clone().is_null());
+    }
+
+    #[test]
+    fn clone_from_reuses_the_existing_heap_allocation() {
+        let source = BlackBox::new(vec![1, 2, 3]);
+        let mut target = BlackBox::new(Vec::with_capacity(8));
+        let heap_address: *const Vec<i32> = &*target;
+
+        target.clone_from(&source);
+        assert_eq!(&*target as *const Vec<i32>, heap_address);
+        assert_eq!(*target, [1, 2, 3]);
+
+        let mut target: BlackBox<Vec<i32>> = BlackBox::empty();
+        target.clone_from(&source);
+        assert_eq!(*target, [1, 2, 3]);
+
+        target.clone_from(&BlackBox::empty());
+        assert!(target.is_null());
+    }
+
+    #[test]
+    fn slice_and_str_payloads() {
+        let slice_box = BlackBox::from_slice(&[1, 2, 3]);
+        assert_eq!(&*slice_box, [1, 2, 3]);
+        assert_eq!(slice_box.len(), 3);
+
+        let vec_box: BlackBox<[String]> = BlackBox::from(vec!["a".to_owned(), "b".to_owned()]);
+        assert_eq!(format!("{:?}", vec_box), r#"BlackBox { large_data_on_the_heap: Some(["a", "b"]) }"#);
+
+        let str_box: BlackBox<str> = "Very large string data".parse().unwrap();
+        assert_eq!(&*str_box, "Very large string data");
+
+        let mut str_box = BlackBox::<str>::from("abc".to_owned());
+        str_box.make_ascii_uppercase();
+        assert_eq!(&*str_box, "ABC");
+    }
+
+    #[test]
+    fn unsize_into_a_trait_object_keeps_the_heap_allocation() {
+        trait Shape {
+            fn area(&self) -> u32;
+        }
+
+        struct Square(u32);
+
+        impl Shape for Square {
+            fn area(&self) -> u32 {
+                self.0 * self.0
+            }
+        }
+
+        let black_box = BlackBox::new(Square(3));
+        let heap_address = &*black_box as *const Square as usize;
+
+        let shape_box = black_box.unsize::<dyn Shape>(|boxed| boxed);
+        assert_eq!(shape_box.area(), 9);
+        assert_eq!(&*shape_box as *const dyn Shape as *const () as usize, heap_address);
+
+        let empty = unsafe { BlackBox::from_raw(ptr::null_mut::<Square>() as *mut dyn Shape) };
+        assert!(empty.is_null());
+        assert!(empty.get().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "BlackBox holds a null pointer")]
+    fn unsize_panics_on_the_null_pointer_state() {
+        BlackBox::<u8>::empty().unsize::<dyn Any>(|boxed| boxed);
+    }
+
+    #[test]
+    fn drop_runs_for_unsized_payloads() {
+        let counter = Cell::new(0);
+
+        let slice_box: BlackBox<[DropCounter]> = BlackBox::from(vec![
+            DropCounter { counter: &counter },
+            DropCounter { counter: &counter },
+        ]);
+        drop(slice_box);
+        assert_eq!(counter.get(), 2);
+
+        let dyn_box = BlackBox::new(DropCounter { counter: &counter })
+            .unsize::<dyn fmt::Debug + '_>(|boxed| boxed);
+        drop(dyn_box);
+        assert_eq!(counter.get(), 3);
+    }
+
+    #[test]
+    fn black_box_is_one_raw_pointer() {
+        assert_eq!(mem::size_of::<BlackBox<String>>(), mem::size_of::<usize>());
+        assert_eq!(mem::size_of::<BlackBox<[u8; 4096]>>(), mem::size_of::<*mut u8>());
+        assert_eq!(mem::align_of::<BlackBox<u8>>(), mem::align_of::<*mut u8>());
+        assert!(BlackBox::<DropCounter>::is_pointer_sized());
+
+        // Unsized `T` makes it a fat pointer, `ThinBlackBox` is the one word option.
+        assert_eq!(mem::size_of::<BlackBox<str>>(), 2 * mem::size_of::<usize>());
+    }
+
+    #[test]
+    #[cfg(not(feature = "allocator-api"))]
+    fn black_box_crosses_ffi_as_a_plain_pointer() {
+        extern "C" fn sum_and_free(black_box: BlackBox<[u64; 2]>) -> u64 {
+            black_box.get().map_or(0, |pair| pair[0] + pair[1])
+        }
+
+        // Call it the way C code would, with a raw pointer which owns the heap value.
+        let as_seen_from_c: extern "C" fn(*mut [u64; 2]) -> u64 =
+            unsafe { mem::transmute(sum_and_free as extern "C" fn(BlackBox<[u64; 2]>) -> u64) };
+
+        let mut black_box = mem::ManuallyDrop::new(BlackBox::new([1, 2]));
+        assert_eq!(as_seen_from_c(black_box.as_mut_ptr()), 3);
+        assert_eq!(as_seen_from_c(std::ptr::null_mut()), 0);
+    }
+
+    #[test]
+    fn huge_array_never_touches_a_tiny_stack() {
+        const LEN: usize = 64 << 20;
+
+        let handle = std::thread::Builder::new()
+            .stack_size(8 * 1024)
+            .spawn(|| {
+                let zeroed = unsafe { BlackBox::<[u8; LEN]>::new_zeroed().assume_init() };
+                assert_eq!((zeroed[0], zeroed[LEN / 2], zeroed[LEN - 1]), (0, 0, 0));
+
+                let mut uninit = BlackBox::<[u8; LEN]>::new_uninit();
+                unsafe { uninit.as_mut_ptr().cast::<u8>().write_bytes(7, LEN) };
+                let filled = unsafe { uninit.assume_init() };
+                assert_eq!((filled[0], filled[LEN / 2], filled[LEN - 1]), (7, 7, 7));
+            })
+            .unwrap();
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn new_with_and_write_initialise_the_heap_allocation() {
+        let counter = Cell::new(0);
+
+        let black_box = BlackBox::new_with(|| DropCounter { counter: &counter });
+        assert_eq!(counter.get(), 0);
+        drop(black_box);
+        assert_eq!(counter.get(), 1);
+
+        let black_box = BlackBox::<String>::new_uninit().write("Very large string data".to_owned());
+        assert_eq!(*black_box, "Very large string data");
+
+        // Zero-sized types are never allocated.
+        let unit = unsafe { BlackBox::<()>::new_zeroed().assume_init() };
+        assert_eq!(*unit, ());
+    }
+
+    #[test]
+    fn black_box_moves_across_threads() {
+        let mut black_box = BlackBox::new(vec![1, 2, 3]);
+
+        black_box = std::thread::spawn(move || {
+            black_box.push(4);
+            black_box
+        })
+        .join()
+        .unwrap();
+        assert_eq!(*black_box, [1, 2, 3, 4]);
+
+        // Shared between threads by reference.
+        let shared = BlackBox::new(std::sync::atomic::AtomicUsize::new(0));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| shared.fetch_add(1, std::sync::atomic::Ordering::SeqCst));
+            }
+        });
+        assert_eq!(shared.into_inner().into_inner(), 4);
+
+        let str_box: BlackBox<str> = BlackBox::from("Very large string data");
+        let len = std::thread::spawn(move || str_box.len()).join().unwrap();
+        assert_eq!(len, 22);
+    }
+
+    #[test]
+    fn comparisons_look_through_the_pointer() {
+        let mut boxes = [BlackBox::new(3), BlackBox::empty(), BlackBox::new(1), BlackBox::new(2)];
+        boxes.sort();
+
+        // The null pointer sorts first, like `None`.
+        assert!(boxes[0].is_null());
+        assert_eq!(boxes[1..].iter().map(|black_box| **black_box).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(BlackBox::new(1) == BlackBox::new(1));
+        assert!(BlackBox::<i32>::empty() == BlackBox::empty());
+        assert!(BlackBox::new(f64::NAN).partial_cmp(&BlackBox::new(1.0)).is_none());
+
+        let str_box: BlackBox<str> = BlackBox::from("b");
+        assert!(str_box > BlackBox::from("a"));
+    }
+
+    #[test]
+    fn black_box_works_as_a_map_key() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashMap;
+        use std::hash::{Hash, Hasher};
+
+        fn hash_of(value: &impl Hash) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let mut map = HashMap::new();
+        map.insert(BlackBox::new("key".to_owned()), 1);
+        assert_eq!(map.get(&BlackBox::new("key".to_owned())), Some(&1));
+        assert_eq!(hash_of(&BlackBox::new("key".to_owned())), hash_of(&"key".to_owned()));
+    }
+
+    #[test]
+    fn ptr_eq_compares_identity() {
+        let first = BlackBox::new(1);
+        let second = BlackBox::new(1);
+
+        assert!(first == second);
+        assert!(!first.ptr_eq(&second));
+        assert!(first.ptr_eq(&first));
+        assert!(BlackBox::<i32>::empty().ptr_eq(&BlackBox::empty()));
+    }
+
+    #[test]
+    fn display_and_pointer_formatting() {
+        let black_box = BlackBox::new(42);
+        let str_box: BlackBox<str> = BlackBox::from("Very large string data");
+
+        assert_eq!(format!("{:>4}", black_box), "  42");
+        assert_eq!(str_box.to_string(), "Very large string data");
+        assert_eq!(format!("{:p}", black_box), format!("{:p}", &*black_box));
+        assert_eq!(format!("{:p}", str_box), format!("{:p}", str_box.as_ptr()));
+        assert_eq!(format!("{:p}", BlackBox::<i32>::empty()), "0x0");
+    }
+
+    #[test]
+    fn black_box_in_generic_apis() {
+        use std::borrow::{Borrow, BorrowMut};
+        use std::collections::HashMap;
+
+        fn shout(value: &mut impl BorrowMut<String>) {
+            value.borrow_mut().make_ascii_uppercase();
+        }
+
+        let mut map: HashMap<BlackBox<String>, usize> = HashMap::new();
+        map.insert(BlackBox::from("key".to_owned()), 1);
+        assert_eq!(map.get("key"), Some(&1));
+
+        let mut black_box: BlackBox<String> = Default::default();
+        assert!(!black_box.is_null());
+        black_box.as_mut().push_str("abc");
+        shout(&mut black_box);
+        assert_eq!(black_box.as_ref(), "ABC");
+        assert_eq!(Borrow::<str>::borrow(&black_box), "ABC");
+
+        let vec_box = BlackBox::new(vec![1, 2]);
+        assert_eq!(Borrow::<[i32]>::borrow(&vec_box), [1, 2]);
+    }
+
+    #[test]
+    fn null_map_keys_only_work_by_black_box() {
+        use std::collections::BTreeMap;
+
+        let mut map: BTreeMap<BlackBox<String>, usize> = BTreeMap::new();
+        map.insert(BlackBox::empty(), 0);
+        map.insert(BlackBox::from("key".to_owned()), 1);
+        assert_eq!(map.get(&BlackBox::empty()), Some(&0));
+        assert_eq!(map.get(&BlackBox::from("key".to_owned())), Some(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = "BlackBox holds a null pointer")]
+    fn null_map_keys_panic_on_borrowed_lookups() {
+        use std::collections::HashMap;
+        use std::hash::{BuildHasherDefault, Hasher};
+
+        // Every key gets the same hash, so the lookup always compares against the
+        // null key.
+        #[derive(Default)]
+        struct SameHash;
+
+        impl Hasher for SameHash {
+            fn finish(&self) -> u64 {
+                0
+            }
+
+            fn write(&mut self, _: &[u8]) {}
+        }
+
+        let mut map: HashMap<BlackBox<String>, usize, BuildHasherDefault<SameHash>> =
+            HashMap::default();
+        map.insert(BlackBox::empty(), 0);
+        map.get("key");
+    }
+
+    #[test]
+    fn debug_layout_works_for_any_payload() {
+        struct NoDebug(#[allow(dead_code)] u64);
+
+        let black_box = BlackBox::new(NoDebug(1));
+        let short = format!("{:?}", black_box.debug_layout());
+        let type_name = std::any::type_name::<NoDebug>();
+        assert_eq!(short, format!("BlackBox<{}>({:p})", type_name, black_box));
+
+        let slice_box: BlackBox<[u32]> = BlackBox::from_vec(vec![1, 2, 3]);
+        let detailed = format!("{:#?}", slice_box.debug_layout());
+        assert!(detailed.contains("type: [u32]"));
+        assert!(detailed.contains(&format!("address: {:p}", slice_box)));
+        assert!(detailed.contains("is_null: false"));
+        assert!(detailed.contains("size: 12"));
+        assert!(detailed.contains("align: 4"));
+
+        let dyn_box = BlackBox::new(NoDebug(2)).unsize::<dyn Send>(|boxed| boxed);
+        assert!(format!("{:#?}", dyn_box.debug_layout()).contains("size: 8"));
+
+        let empty = BlackBox::<NoDebug>::empty();
+        assert!(format!("{:?}", empty.debug_layout()).ends_with("(0x0)"));
+        let detailed = format!("{:#?}", empty.debug_layout());
+        assert!(detailed.contains("is_null: true"));
+        assert!(!detailed.contains("size"));
+    }
+
+    /// Points into itself, so it must never move once `self_ref` is set.
+    struct SelfReferential {
+        data: String,
+        self_ref: *const String,
+        _pinned: std::marker::PhantomPinned,
+    }
+
+    impl SelfReferential {
+        fn pinned(data: &str) -> Pin<BlackBox<SelfReferential>> {
+            let mut pinned = BlackBox::pin(SelfReferential {
+                data: data.to_owned(),
+                self_ref: ptr::null(),
+                _pinned: std::marker::PhantomPinned,
+            });
+
+            // Safety: only a field is written, nothing is moved. The raw pointer comes
+            // from the same mutable reference, so it stays valid under Miri as well.
+            let this = unsafe { pinned.as_mut().get_unchecked_mut() };
+            this.self_ref = &this.data;
+            pinned
+        }
+
+        fn data_through_self_ref(&self) -> &str {
+            // Safety: `self` is pinned, `self_ref` still points to `self.data`.
+            unsafe { &*self.self_ref }
+        }
+    }
+
+    #[test]
+    fn pinned_self_referential_struct_survives_moves_of_the_handle() {
+        let first = SelfReferential::pinned("first");
+        let mut second = SelfReferential::pinned("second");
+        let first_address: *const String = &first.data;
+
+        // Moving and swapping the handles never moves the heap values.
+        let mut handles = vec![first];
+        mem::swap(&mut handles[0], &mut second);
+        let first = second;
+        let second = handles.pop().unwrap();
+
+        assert_eq!(&first.data as *const String, first_address);
+        assert!(ptr::eq(first.self_ref, &first.data));
+        assert_eq!(first.data_through_self_ref(), "first");
+        assert_eq!(second.data_through_self_ref(), "second");
+
+        let pinned: Pin<BlackBox<u8>> = BlackBox::new(1).into();
+        assert_eq!(*pinned, 1);
+    }
+
+    #[test]
+    fn black_box_is_always_unpin() {
+        fn assert_unpin<T: Unpin>() {}
+
+        assert_unpin::<BlackBox<SelfReferential>>();
+        assert_unpin::<BlackBox<dyn Future<Output = ()>>>();
+    }
+
+    #[test]
+    fn black_box_polls_the_heap_future() {
+        use std::sync::Arc;
+        use std::task::{Wake, Waker};
+
+        struct NoopWaker;
+
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        /// Pending for the first `n` polls, ready after that.
+        struct Countdown(u32);
+
+        impl Future for Countdown {
+            type Output = &'static str;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                if self.0 == 0 {
+                    return Poll::Ready("done");
+                }
+                self.0 -= 1;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        // `Unpin` futures are polled through the `BlackBox` directly.
+        let mut countdown = BlackBox::new(Countdown(2));
+        assert_eq!(Pin::new(&mut countdown).poll(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut countdown).poll(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut countdown).poll(&mut cx), Poll::Ready("done"));
+
+        // `!Unpin` ones (like `async` blocks) once they're pinned, trait objects too.
+        let mut pinned: Pin<BlackBox<dyn Future<Output = &str>>> =
+            BlackBox::new(async {
+                let message = Countdown(1).await;
+                &message[..2]
+            })
+                .unsize::<dyn Future<Output = &str>>(|boxed| boxed)
+                .into_pin();
+        assert_eq!(pinned.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(pinned.as_mut().poll(&mut cx), Poll::Ready("do"));
+    }
+
+    #[test]
+    fn downcast_keeps_the_heap_allocation() {
+        let mut registry: Vec<BlackBox<dyn Any>> = vec![
+            BlackBox::new([7u64; 128]).unsize::<dyn Any>(|boxed| boxed),
+            BlackBox::new("plugin".to_owned()).unsize::<dyn Any>(|boxed| boxed),
+        ];
+
+        assert!(registry[0].is::<[u64; 128]>());
+        assert_eq!(registry[1].downcast_ref::<String>().unwrap(), "plugin");
+        assert!(registry[1].downcast_ref::<&str>().is_none());
+        registry[1].downcast_mut::<String>().unwrap().push('!');
+
+        let plugin = registry.pop().unwrap();
+        let address = plugin.address();
+        let plugin = plugin.downcast::<u32>().unwrap_err();
+        let plugin = plugin.downcast::<String>().unwrap();
+        assert_eq!(&*plugin as *const String as *const u8, address);
+        assert_eq!(*plugin, "plugin!");
+
+        let empty = unsafe { BlackBox::from_raw(ptr::null_mut::<u8>() as *mut dyn Any) };
+        assert!(!empty.is::<u8>());
+        assert!(empty.downcast_ref::<u8>().is_none());
+        assert!(empty.downcast::<u8>().unwrap_err().is_null());
+    }
+
+    #[test]
+    fn downcast_send_payloads_across_threads() {
+        let payload = BlackBox::new(vec![1, 2, 3]).unsize::<dyn Any + Send>(|boxed| boxed);
+        let payload = std::thread::spawn(move || payload).join().unwrap();
+        assert_eq!(*payload.downcast::<Vec<i32>>().unwrap(), [1, 2, 3]);
+
+        let shared = BlackBox::new(42).unsize::<dyn Any + Send + Sync>(|boxed| boxed);
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert_eq!(shared.downcast_ref::<i32>(), Some(&42)));
+        });
+    }
+
+    #[test]
+    fn raw_pointer_round_trips_keep_the_heap_allocation() {
+        let counter = Cell::new(0);
+
+        let black_box = BlackBox::new(DropCounter { counter: &counter });
+        let address = black_box.as_ptr();
+        let raw = black_box.into_raw();
+        assert_eq!(raw as *const DropCounter, address);
+        assert_eq!(counter.get(), 0);
+
+        let black_box = unsafe { BlackBox::from_raw(raw) };
+        assert_eq!(black_box.as_ptr(), address);
+        drop(black_box);
+        assert_eq!(counter.get(), 1);
+
+        // The null pointer state round trips as a null raw pointer.
+        let empty = BlackBox::<u64>::empty();
+        assert!(empty.as_ptr().is_null());
+        assert!(empty.into_raw().is_null());
+        assert!(unsafe { BlackBox::<u64>::from_raw(ptr::null_mut()) }.is_null());
+        let raw = ptr::slice_from_raw_parts_mut(ptr::null_mut::<u64>(), 3);
+        assert!(unsafe { BlackBox::<[u64]>::from_raw(raw) }.into_non_null().is_none());
+
+        // Unsized heap values keep their metadata.
+        let str_box: BlackBox<str> = BlackBox::from("Very large string data");
+        let non_null = str_box.into_non_null().unwrap();
+        let str_box = unsafe { BlackBox::from_raw(non_null.as_ptr()) };
+        assert_eq!(&*str_box, "Very large string data");
+    }
+
+    #[test]
+    fn raw_pointers_interoperate_with_box() {
+        let raw = Box::into_raw(Box::new("Very large string data".to_owned()));
+        let black_box = unsafe { BlackBox::from_raw(raw) };
+        assert_eq!(*black_box, "Very large string data");
+
+        let boxed = unsafe { Box::from_raw(black_box.into_raw()) };
+        assert_eq!(&*boxed as *const String, raw as *const String);
+    }
+
+    #[test]
+    fn ownership_goes_through_ffi_and_back() {
+        /// Takes over the ownership on the other side of the FFI boundary.
+        extern "C" fn consume(raw: *mut [u64; 2]) -> u64 {
+            let black_box = unsafe { BlackBox::from_raw(raw) };
+            black_box.iter().sum()
+        }
+
+        assert_eq!(consume(BlackBox::new([40u64, 2]).into_raw()), 42);
+    }
+
+    #[test]
+    fn leak_lives_until_taken_back() {
+        let counter = Cell::new(0);
+
+        let leaked: &mut DropCounter = BlackBox::new(DropCounter { counter: &counter }).leak();
+        let raw: *mut DropCounter = leaked;
+        assert_eq!(counter.get(), 0);
+
+        drop(unsafe { BlackBox::from_raw(raw) });
+        assert_eq!(counter.get(), 1);
+
+        let forever: &'static mut Vec<u8> = BlackBox::new(vec![1]).leak();
+        forever.push(2);
+        assert_eq!(*forever, [1, 2]);
+        drop(unsafe { BlackBox::from_raw(forever) });
+    }
+
+    #[test]
+    #[should_panic(expected = "null pointer")]
+    fn leak_panics_on_the_null_pointer() {
+        BlackBox::<u64>::empty().leak();
+    }
 }
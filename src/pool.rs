@@ -0,0 +1,273 @@
+use crate::allocator::{self, Global};
+use core::cell::{Cell, RefCell};
+use core::ptr::{self, NonNull};
+use std::alloc::Layout;
+use std::fmt;
+
+/// How well a `BlackBoxPool` is doing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// `alloc()` calls which reused a recycled heap slot.
+    pub hits: usize,
+    /// `alloc()` calls which had to allocate a new heap slot.
+    pub misses: usize,
+    /// The largest number of `PooledBlackBox` alive at the same time.
+    pub high_water_mark: usize,
+}
+
+/// Recycles the heap slots of `BlackBox`-like values to get rid of the allocation
+/// churn when many short-lived large data sets of the same type come and go.
+///
+/// `alloc()` moves the value into a recycled heap slot if there is one, otherwise
+/// into a newly allocated one, and returns a `PooledBlackBox`. When that is
+/// dropped, the value is dropped as usual, but its heap slot goes back to the pool
+/// for the next `alloc()`, unless the pool already keeps `capacity` idle slots.
+///
+/// The optional reset hook runs on every value right before it's dropped, e.g. to
+/// wipe sensitive data which would otherwise stay in the recycled heap slot.
+pub struct BlackBoxPool<T> {
+    /// The idle heap slots, uninitialised memory for a `T` each.
+    idle: RefCell<Vec<NonNull<T>>>,
+    /// The most idle heap slots to keep, the others are freed.
+    capacity: usize,
+    reset_hook: Option<ResetHook<T>>,
+    /// How many `PooledBlackBox` are alive.
+    live: Cell<usize>,
+    stats: Cell<PoolStats>,
+}
+
+/// Runs on every value right before it's dropped.
+type ResetHook<T> = Box<dyn Fn(&mut T)>;
+
+impl<T> BlackBoxPool<T> {
+    /// Creating an empty pool which keeps up to `capacity` idle heap slots.
+    pub fn new(capacity: usize) -> Self {
+        BlackBoxPool {
+            idle: RefCell::new(Vec::with_capacity(capacity)),
+            capacity,
+            reset_hook: None,
+            live: Cell::new(0),
+            stats: Cell::new(PoolStats::default()),
+        }
+    }
+
+    /// Same with `new()`, and `reset_hook` runs on every value right before it's
+    /// dropped and its heap slot goes back to the pool.
+    pub fn with_reset_hook(capacity: usize, reset_hook: impl Fn(&mut T) + 'static) -> Self {
+        let mut pool = BlackBoxPool::new(capacity);
+        pool.reset_hook = Some(Box::new(reset_hook));
+        pool
+    }
+
+    /// Moves `value` into a recycled heap slot, or a new one if none is idle.
+    pub fn alloc(&self, value: T) -> PooledBlackBox<'_, T> {
+        let mut stats = self.stats.get();
+
+        let slot = match self.idle.borrow_mut().pop() {
+            Some(slot) => {
+                stats.hits += 1;
+                slot
+            }
+            None => {
+                stats.misses += 1;
+                match allocator::allocate::<T, _>(&Global, false) {
+                    Ok(slot) => slot,
+                    Err(error) => allocator::handle_alloc_error(error),
+                }
+            }
+        };
+
+        // Safety: an idle heap slot holds no value.
+        unsafe { slot.as_ptr().write(value) };
+
+        self.live.set(self.live.get() + 1);
+        stats.high_water_mark = stats.high_water_mark.max(self.live.get());
+        self.stats.set(stats);
+
+        PooledBlackBox {
+            heap_value: slot,
+            pool: self,
+        }
+    }
+
+    /// The most idle heap slots the pool keeps.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How many idle heap slots are ready for the next `alloc()`.
+    pub fn idle(&self) -> usize {
+        self.idle.borrow().len()
+    }
+
+    /// The statistics since the pool was created.
+    pub fn stats(&self) -> PoolStats {
+        self.stats.get()
+    }
+
+    /// Drops the value in `slot` and keeps the heap slot if there is room.
+    fn recycle(&self, slot: NonNull<T>) {
+        self.live.set(self.live.get() - 1);
+
+        // Safety: `slot` holds a value which nobody else is looking at.
+        unsafe {
+            if let Some(reset_hook) = &self.reset_hook {
+                reset_hook(&mut *slot.as_ptr());
+            }
+            ptr::drop_in_place(slot.as_ptr());
+        }
+
+        let mut idle = self.idle.borrow_mut();
+        if idle.len() < self.capacity {
+            idle.push(slot);
+        } else {
+            // Safety: the slot came from `Global` and holds no value any more.
+            unsafe { allocator::deallocate(&Global, slot.cast(), Layout::new::<T>()) };
+        }
+    }
+}
+
+impl<T> fmt::Debug for BlackBoxPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlackBoxPool")
+            .field("capacity", &self.capacity)
+            .field("idle", &self.idle())
+            .field("live", &self.live.get())
+            .field("stats", &self.stats.get())
+            .finish()
+    }
+}
+
+/// Frees the idle heap slots. No `PooledBlackBox` can be alive by now, as they
+/// borrow the pool.
+impl<T> Drop for BlackBoxPool<T> {
+    fn drop(&mut self) {
+        for slot in self.idle.get_mut().drain(..) {
+            unsafe { allocator::deallocate(&Global, slot.cast(), Layout::new::<T>()) };
+        }
+    }
+}
+
+/// The handle returned by `BlackBoxPool::alloc()`, its heap slot goes back to the
+/// pool when it's dropped.
+pub struct PooledBlackBox<'pool, T> {
+    heap_value: NonNull<T>,
+    pool: &'pool BlackBoxPool<T>,
+}
+
+impl<T> std::ops::Deref for PooledBlackBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the heap value lives until `self` is dropped.
+        unsafe { self.heap_value.as_ref() }
+    }
+}
+
+impl<T> std::ops::DerefMut for PooledBlackBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: `&mut self` makes sure nobody else is looking at the heap value.
+        unsafe { self.heap_value.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PooledBlackBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledBlackBox").field(&**self).finish()
+    }
+}
+
+impl<T> Drop for PooledBlackBox<'_, T> {
+    fn drop(&mut self) {
+        self.pool.recycle(self.heap_value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn slots_are_recycled() {
+        let pool = BlackBoxPool::new(2);
+
+        let first = pool.alloc([1u64; 64]);
+        let first_address = &*first as *const [u64; 64];
+        drop(first);
+        assert_eq!(pool.idle(), 1);
+
+        let second = pool.alloc([2u64; 64]);
+        assert_eq!(&*second as *const [u64; 64], first_address);
+        assert_eq!(second[63], 2);
+
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                hits: 1,
+                misses: 1,
+                high_water_mark: 1
+            }
+        );
+    }
+
+    #[test]
+    fn capacity_limits_the_idle_slots() {
+        let pool = BlackBoxPool::new(2);
+
+        let boxes: Vec<PooledBlackBox<String>> =
+            (0..5).map(|i| pool.alloc(i.to_string())).collect();
+        assert_eq!(boxes.iter().map(|value| value.as_str()).collect::<String>(), "01234");
+        drop(boxes);
+
+        assert_eq!(pool.idle(), 2);
+        assert_eq!(pool.stats().high_water_mark, 5);
+        assert_eq!(pool.stats().misses, 5);
+
+        let _reused = (pool.alloc("a".to_owned()), pool.alloc("b".to_owned()));
+        let _fresh = pool.alloc("c".to_owned());
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                hits: 2,
+                misses: 6,
+                high_water_mark: 5
+            }
+        );
+    }
+
+    #[test]
+    fn reset_hook_runs_before_the_value_is_dropped() {
+        let dropped = Rc::new(Cell::new(0));
+        let dropped_in_hook = dropped.clone();
+
+        let pool = BlackBoxPool::with_reset_hook(1, move |value: &mut Vec<u8>| {
+            assert_eq!(dropped_in_hook.get(), 0);
+            value.iter_mut().for_each(|byte| *byte = 0);
+            dropped_in_hook.set(value.len());
+        });
+
+        let mut secret = pool.alloc(vec![1, 2, 3]);
+        secret.push(4);
+        drop(secret);
+
+        assert_eq!(dropped.get(), 4);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn values_are_dropped_exactly_once() {
+        let value = Rc::new(());
+        {
+            let pool = BlackBoxPool::new(1);
+            let first = pool.alloc(value.clone());
+            let second = pool.alloc(value.clone());
+            assert_eq!(Rc::strong_count(&value), 3);
+            drop((first, second));
+            assert_eq!(Rc::strong_count(&value), 1);
+
+            let _third = pool.alloc(value.clone());
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,432 @@
+use core::mem::ManuallyDrop;
+use core::hint;
+use core::ptr::NonNull;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+use std::fmt;
+
+/// The same limit as `Arc`, far below `usize::MAX` so an overflow can be caught
+/// before it happens.
+const MAX_REFCOUNT: usize = isize::MAX as usize;
+
+/// A reference-counted `BlackBox`: many handles share one heap value, and it's
+/// dropped when the last `SharedBlackBox` goes away.
+///
+/// Unlike `Arc<BlackBox<T>>`, there is only one indirection: the strong and weak
+/// counts live in the same heap allocation as the value, and the handle is still
+/// one pointer wide:
+///
+/// ```text
+/// SharedBlackBox ----> [ strong | weak | heap value ]
+/// ```
+///
+/// `WeakBlackBox` handles don't keep the heap value alive, `upgrade()` them to
+/// get a `SharedBlackBox` back as long as the value is still there.
+pub struct SharedBlackBox<T> {
+    inner: NonNull<SharedInner<T>>,
+}
+
+/// The weak version of `SharedBlackBox`, created by `SharedBlackBox::downgrade()`.
+pub struct WeakBlackBox<T> {
+    inner: NonNull<SharedInner<T>>,
+}
+
+struct SharedInner<T> {
+    /// The number of `SharedBlackBox` handles.
+    strong: AtomicUsize,
+    /// The number of `WeakBlackBox` handles, plus 1 for all the `SharedBlackBox`
+    /// handles together, which keeps the heap allocation alive until the heap
+    /// value is dropped.
+    weak: AtomicUsize,
+    /// Dropped by hand when `strong` drops to 0, while the allocation stays alive
+    /// as long as `weak` is not 0.
+    value: ManuallyDrop<T>,
+}
+
+impl<T> SharedBlackBox<T> {
+    /// Creating instance, and the `large_data_set`'s ownership will be moved into
+    /// the created instance.
+    pub fn new(large_data_set: T) -> Self {
+        let inner = Box::new(SharedInner {
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            value: ManuallyDrop::new(large_data_set),
+        });
+
+        SharedBlackBox {
+            inner: NonNull::from(Box::leak(inner)),
+        }
+    }
+
+    fn inner(&self) -> &SharedInner<T> {
+        // Safety: the heap allocation lives as long as any handle.
+        unsafe { self.inner.as_ref() }
+    }
+
+    /// Creating a `WeakBlackBox` to the same heap value.
+    pub fn downgrade(&self) -> WeakBlackBox<T> {
+        let weak = &self.inner().weak;
+        let mut count = weak.load(Ordering::Relaxed);
+
+        loop {
+            // `get_mut()` on another handle has locked the weak count, wait for it.
+            if count == usize::MAX {
+                hint::spin_loop();
+                count = weak.load(Ordering::Relaxed);
+                continue;
+            }
+            if count > MAX_REFCOUNT {
+                std::process::abort();
+            }
+
+            match weak.compare_exchange_weak(
+                count,
+                count + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return WeakBlackBox { inner: self.inner },
+                Err(current) => count = current,
+            }
+        }
+    }
+
+    /// The number of `SharedBlackBox` handles to the heap value.
+    pub fn strong_count(&self) -> usize {
+        self.inner().strong.load(Ordering::Acquire)
+    }
+
+    /// The number of `WeakBlackBox` handles to the heap value.
+    pub fn weak_count(&self) -> usize {
+        match self.inner().weak.load(Ordering::Acquire) {
+            // Locked by `get_mut()`, which only happens while there is none.
+            usize::MAX => 0,
+            count => count - 1,
+        }
+    }
+
+    /// Returns `true` if both handles share the same heap value.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+
+    /// Returns the mutable heap value reference if this is the only handle (weak
+    /// ones included), otherwise `None`.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.is_unique() {
+            // Safety: this is the only handle.
+            Some(unsafe { &mut (*self.inner.as_ptr()).value })
+        } else {
+            None
+        }
+    }
+
+    /// Same as `Arc::is_unique`: checking `strong` and `weak` one after another
+    /// isn't enough, as a `WeakBlackBox` could `upgrade()` in between and then be
+    /// dropped. So the weak count is locked first (only possible when there is no
+    /// `WeakBlackBox`), then no new `WeakBlackBox` can show up while `strong` is
+    /// checked, and `&mut self` means this handle can't be cloned either.
+    fn is_unique(&mut self) -> bool {
+        let weak = &self.inner().weak;
+        if weak
+            .compare_exchange(1, usize::MAX, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+
+        // `Acquire` pairs with the `Release` in `decrement()`, so everything the
+        // other handles did happens before the caller writes to the heap value.
+        let unique = self.inner().strong.load(Ordering::Acquire) == 1;
+
+        // `Release` pairs with the `Acquire` in `downgrade()`.
+        weak.store(1, Ordering::Release);
+        unique
+    }
+}
+
+impl<T: Clone> SharedBlackBox<T> {
+    /// Copy-on-write: returns the mutable heap value reference, cloning the heap
+    /// value into a new heap allocation first if other handles share it. Weak
+    /// handles to the old heap value stay with the old heap value.
+    pub fn make_mut(&mut self) -> &mut T {
+        if self.get_mut().is_none() {
+            *self = SharedBlackBox::new((**self).clone());
+        }
+
+        // Safety: a fresh or unique handle is the only one.
+        unsafe { &mut (*self.inner.as_ptr()).value }
+    }
+}
+
+impl<T> WeakBlackBox<T> {
+    fn inner(&self) -> &SharedInner<T> {
+        // Safety: the heap allocation lives as long as any handle.
+        unsafe { self.inner.as_ref() }
+    }
+
+    /// Returns a `SharedBlackBox` to the heap value, or `None` if it's gone.
+    pub fn upgrade(&self) -> Option<SharedBlackBox<T>> {
+        let strong = &self.inner().strong;
+        let mut count = strong.load(Ordering::Relaxed);
+
+        // Only increment while it's not 0, once it's 0 the heap value is dropped.
+        loop {
+            if count == 0 {
+                return None;
+            }
+            if count > MAX_REFCOUNT {
+                std::process::abort();
+            }
+
+            match strong.compare_exchange_weak(
+                count,
+                count + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(SharedBlackBox { inner: self.inner }),
+                Err(current) => count = current,
+            }
+        }
+    }
+
+    /// The number of `SharedBlackBox` handles to the heap value.
+    pub fn strong_count(&self) -> usize {
+        self.inner().strong.load(Ordering::Acquire)
+    }
+}
+
+/// Bumps a count, aborting on overflow like `Arc` does, as the handles could
+/// otherwise free the heap value while it's still in use.
+fn increment(count: &AtomicUsize) {
+    // `Relaxed` is enough, the handle we clone from already keeps the heap alive.
+    if count.fetch_add(1, Ordering::Relaxed) > MAX_REFCOUNT {
+        std::process::abort();
+    }
+}
+
+/// Drops one count, returns `true` if it was the last one. Everything other
+/// threads did with the heap value happens before the caller frees it.
+fn decrement(count: &AtomicUsize) -> bool {
+    if count.fetch_sub(1, Ordering::Release) != 1 {
+        return false;
+    }
+
+    atomic::fence(Ordering::Acquire);
+    true
+}
+
+/// A new handle to the same heap value, nothing is copied.
+impl<T> Clone for SharedBlackBox<T> {
+    fn clone(&self) -> Self {
+        increment(&self.inner().strong);
+        SharedBlackBox { inner: self.inner }
+    }
+}
+
+impl<T> Clone for WeakBlackBox<T> {
+    fn clone(&self) -> Self {
+        increment(&self.inner().weak);
+        WeakBlackBox { inner: self.inner }
+    }
+}
+
+impl<T> std::ops::Deref for SharedBlackBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner().value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SharedBlackBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedBlackBox").field(&**self).finish()
+    }
+}
+
+impl<T> fmt::Debug for WeakBlackBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(WeakBlackBox)")
+    }
+}
+
+/// Shared between threads like `Arc<T>`, which needs `T` to be both.
+unsafe impl<T: Send + Sync> Send for SharedBlackBox<T> {}
+unsafe impl<T: Send + Sync> Sync for SharedBlackBox<T> {}
+unsafe impl<T: Send + Sync> Send for WeakBlackBox<T> {}
+unsafe impl<T: Send + Sync> Sync for WeakBlackBox<T> {}
+
+/// The last `SharedBlackBox` drops the heap value, then gives up the weak count
+/// which all the `SharedBlackBox` handles share.
+impl<T> Drop for SharedBlackBox<T> {
+    fn drop(&mut self) {
+        if !decrement(&self.inner().strong) {
+            return;
+        }
+
+        // Safety: this was the last `SharedBlackBox`, nobody can reach the value.
+        unsafe { ManuallyDrop::drop(&mut (*self.inner.as_ptr()).value) };
+        drop(WeakBlackBox { inner: self.inner });
+    }
+}
+
+/// The last handle of any kind frees the heap allocation.
+impl<T> Drop for WeakBlackBox<T> {
+    fn drop(&mut self) {
+        if decrement(&self.inner().weak) {
+            // Safety: no handle is left, and the value is already dropped.
+            unsafe { drop(Box::from_raw(self.inner.as_ptr())) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+    use std::sync::Arc;
+
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn handles_are_one_pointer_wide() {
+        assert_eq!(mem::size_of::<SharedBlackBox<String>>(), mem::size_of::<usize>());
+        assert_eq!(mem::size_of::<Option<SharedBlackBox<String>>>(), mem::size_of::<usize>());
+        assert_eq!(mem::size_of::<WeakBlackBox<String>>(), mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn clone_shares_the_heap_value() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let first = SharedBlackBox::new(Counted(dropped.clone()));
+        let second = first.clone();
+
+        assert!(first.ptr_eq(&second));
+        assert_eq!(first.strong_count(), 2);
+
+        drop(first);
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        drop(second);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn weak_handles_upgrade_while_the_value_is_alive() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let shared = SharedBlackBox::new(Counted(dropped.clone()));
+        let weak = shared.downgrade();
+        assert_eq!((shared.strong_count(), shared.weak_count()), (1, 1));
+
+        let upgraded = weak.upgrade().unwrap();
+        assert!(upgraded.ptr_eq(&shared));
+        drop((shared, upgraded));
+
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        assert_eq!(weak.strong_count(), 0);
+        assert!(weak.clone().upgrade().is_none());
+    }
+
+    #[test]
+    fn get_mut_only_for_the_only_handle() {
+        let mut shared = SharedBlackBox::new(1);
+        *shared.get_mut().unwrap() += 1;
+
+        let weak = shared.downgrade();
+        assert!(shared.get_mut().is_none());
+        drop(weak);
+
+        let other = shared.clone();
+        assert!(shared.get_mut().is_none());
+        drop(other);
+
+        assert_eq!(shared.get_mut(), Some(&mut 2));
+    }
+
+    #[test]
+    fn make_mut_copies_on_write() {
+        let mut first = SharedBlackBox::new(vec![1, 2]);
+        let second = first.clone();
+        let weak = first.downgrade();
+
+        first.make_mut().push(3);
+        assert!(!first.ptr_eq(&second));
+        assert_eq!((&*first, &*second), (&vec![1, 2, 3], &vec![1, 2]));
+        assert!(weak.upgrade().unwrap().ptr_eq(&second));
+
+        // Unique now, so no copy.
+        let address = &*first as *const Vec<i32>;
+        first.make_mut().push(4);
+        assert_eq!(&*first as *const Vec<i32>, address);
+    }
+
+    #[test]
+    fn shared_across_threads() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let shared = SharedBlackBox::new(Counted(dropped.clone()));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = shared.clone();
+                let weak = shared.downgrade();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        drop(weak.upgrade().unwrap().clone());
+                    }
+                    drop(shared);
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(shared.strong_count(), 1);
+        assert_eq!(shared.weak_count(), 0);
+        drop(shared);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn get_mut_races_with_upgrade_then_drop_weak() {
+        use std::sync::atomic::AtomicBool;
+
+        for _ in 0..500 {
+            let mut shared = SharedBlackBox::new(0);
+            let weak = shared.downgrade();
+            let upgraded_alive = AtomicBool::new(false);
+
+            std::thread::scope(|scope| {
+                let upgraded_alive = &upgraded_alive;
+                scope.spawn(move || {
+                    let upgraded = weak.upgrade().unwrap();
+                    drop(weak);
+                    upgraded_alive.store(true, Ordering::SeqCst);
+                    for _ in 0..100 {
+                        hint::spin_loop();
+                    }
+                    upgraded_alive.store(false, Ordering::SeqCst);
+                    drop(upgraded);
+                });
+
+                // `get_mut()` must not succeed while the upgraded handle is alive.
+                loop {
+                    if let Some(value) = shared.get_mut() {
+                        assert!(!upgraded_alive.load(Ordering::SeqCst));
+                        *value += 1;
+                        break;
+                    }
+                }
+            });
+
+            assert_eq!((*shared, shared.weak_count()), (1, 0));
+        }
+    }
+}
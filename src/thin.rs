@@ -0,0 +1,309 @@
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+use std::alloc::{self, Layout};
+use std::fmt;
+
+/// The `BlackBox` promise of "only one raw pointer" breaks for unsized heap values,
+/// as `NonNull<dyn Trait>` and `NonNull<[T]>` are fat pointers (the data pointer plus
+/// a vtable pointer or a length), so `BlackBox<dyn Trait>` takes 2 words.
+///
+/// `ThinBlackBox` moves that fat pointer into a `Header` at the beginning of the
+/// heap allocation, right before the heap value itself, and only keeps the pointer
+/// to the `Header`:
+///
+/// ```text
+/// ThinBlackBox ----> [ Header { value, layout } | padding | heap value ]
+///                              |                            ^
+///                              +----------------------------+
+/// ```
+///
+/// That's why `ThinBlackBox<T>` is always one word, no matter what `T` is, and
+/// `Option<ThinBlackBox<T>>` is one word as well. The price is one extra memory
+/// read on every dereference, and there is no **null pointer** state.
+pub struct ThinBlackBox<T: ?Sized> {
+    header: NonNull<Header<T>>,
+    // Tells the drop checker that we own a `T`.
+    _marker: PhantomData<T>,
+}
+
+/// Lives at the beginning of the heap allocation.
+struct Header<T: ?Sized> {
+    /// The fat pointer to the heap value, which sits in the same heap allocation.
+    value: NonNull<T>,
+    /// The layout of the whole heap allocation, header included.
+    layout: Layout,
+}
+
+/// The heap allocation of a sized heap value, `repr(C)` keeps the `Header` first.
+#[repr(C)]
+struct Block<T: ?Sized, U> {
+    header: MaybeUninit<Header<T>>,
+    value: U,
+}
+
+/// Frees the block (and drops the value in it) if `coerce` panics.
+struct FreeOnUnwind<T: ?Sized, U>(*mut Block<T, U>);
+
+impl<T: ?Sized, U> Drop for FreeOnUnwind<T, U> {
+    fn drop(&mut self) {
+        unsafe { drop(Box::from_raw(self.0)) }
+    }
+}
+
+impl<T: ?Sized> ThinBlackBox<T> {
+    /// Creating instance from a sized `value`, `coerce` turns the `&mut U` into the
+    /// `&mut T` which is what `ThinBlackBox` stores, that's how a concrete type
+    /// turns into a trait object on stable Rust:
+    ///
+    /// ```
+    /// use raw_pointer_struct_in_rust::ThinBlackBox;
+    /// use std::fmt::Display;
+    ///
+    /// let thin_box = ThinBlackBox::<dyn Display>::new_unsize(1, |value| value);
+    /// assert_eq!(thin_box.to_string(), "1");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `coerce` returns a reference to anything but the whole `value` it
+    /// was given: the address and the size (`size_of_val`) must both be the ones of
+    /// `value`, so a reference to the first field of a larger struct is rejected.
+    ///
+    /// A reference to a field which is as large as `value` itself (e.g. the only
+    /// field of a newtype) still passes, in which case `U`'s own `Drop` never runs,
+    /// only the field's. Return `value` unchanged and let the coercion do the rest.
+    pub fn new_unsize<U>(value: U, coerce: impl FnOnce(&mut U) -> &mut T) -> Self {
+        // Everything below goes through the raw pointer, so the fat pointer we keep
+        // is derived from the same pointer which owns the heap allocation.
+        let block = Box::into_raw(Box::new(Block::<T, U> {
+            header: MaybeUninit::uninit(),
+            value,
+        }));
+        let guard = FreeOnUnwind(block);
+
+        // Safety: `block` is a valid heap allocation and nobody else knows about it.
+        let value = unsafe {
+            let value_address = ptr::addr_of_mut!((*block).value);
+            let value = coerce(&mut *value_address);
+            assert!(
+                value as *mut T as *mut u8 == value_address as *mut u8
+                    && mem::size_of_val(value) == mem::size_of::<U>(),
+                "ThinBlackBox::new_unsize: `coerce` must return the value it was given"
+            );
+            NonNull::from(value)
+        };
+
+        mem::forget(guard);
+        unsafe {
+            ptr::addr_of_mut!((*block).header).write(MaybeUninit::new(Header {
+                value,
+                layout: Layout::new::<Block<T, U>>(),
+            }));
+        }
+
+        // `Header` is the first field of the `repr(C)` block, so the block pointer
+        // is the header pointer.
+        ThinBlackBox {
+            header: unsafe { NonNull::new_unchecked(block as *mut Header<T>) },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the fat pointer to the heap value.
+    fn value_ptr(&self) -> NonNull<T> {
+        // Safety: the header is initialised before `ThinBlackBox` is created.
+        unsafe { (*self.header.as_ptr()).value }
+    }
+}
+
+impl<T> ThinBlackBox<T> {
+    /// Creating instance, and the `large_data_set`'s ownership will be moved into
+    /// the created instance.
+    pub fn new(large_data_set: T) -> Self {
+        ThinBlackBox::new_unsize(large_data_set, |value| value)
+    }
+}
+
+impl<T> ThinBlackBox<[T]> {
+    /// Creating instance by moving every element of `vec` into a new heap allocation
+    /// which holds the slice length in its `Header`.
+    pub fn from_vec(mut vec: Vec<T>) -> Self {
+        let len = vec.len();
+        let (layout, offset) = Layout::new::<Header<[T]>>()
+            .extend(Layout::array::<T>(len).expect("ThinBlackBox: slice too large"))
+            .expect("ThinBlackBox: slice too large");
+        let layout = layout.pad_to_align();
+
+        // Safety: `layout` is never zero-sized, as it contains the `Header`.
+        let block = unsafe { alloc::alloc(layout) };
+        let header = match NonNull::new(block as *mut Header<[T]>) {
+            Some(header) => header,
+            None => alloc::handle_alloc_error(layout),
+        };
+
+        unsafe {
+            // Move the elements, then forget them in `vec` so only its buffer is freed.
+            let data = block.add(offset) as *mut T;
+            ptr::copy_nonoverlapping(vec.as_ptr(), data, len);
+            vec.set_len(0);
+
+            let value = NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(data, len));
+            header.as_ptr().write(Header { value, layout });
+        }
+
+        ThinBlackBox {
+            header,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone> ThinBlackBox<[T]> {
+    /// Creating instance by cloning every element of `slice` into a new heap allocation.
+    pub fn from_slice(slice: &[T]) -> Self {
+        ThinBlackBox::from_vec(slice.to_vec())
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for ThinBlackBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the heap value lives as long as `self`.
+        unsafe { &*self.value_ptr().as_ptr() }
+    }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for ThinBlackBox<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: `&mut self` makes sure nobody else is looking at the heap value.
+        unsafe { &mut *self.value_ptr().as_ptr() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ThinBlackBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ThinBlackBox").field(&&**self).finish()
+    }
+}
+
+/// As thread safe as `T` itself, the same rule as `BlackBox`.
+unsafe impl<T: ?Sized + Send> Send for ThinBlackBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for ThinBlackBox<T> {}
+
+/// Drops the heap value through the fat pointer in the `Header`, then frees the
+/// whole heap allocation with the layout saved in the `Header`.
+impl<T: ?Sized> Drop for ThinBlackBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let Header { value, layout } = self.header.as_ptr().read();
+            ptr::drop_in_place(value.as_ptr());
+            alloc::dealloc(self.header.as_ptr() as *mut u8, layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    trait Shape {
+        fn area(&self) -> u32;
+    }
+
+    struct Square(u32);
+
+    impl Shape for Square {
+        fn area(&self) -> u32 {
+            self.0 * self.0
+        }
+    }
+
+    struct DropCounter<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn thin_box_is_one_word_for_unsized_payloads() {
+        assert_eq!(mem::size_of::<ThinBlackBox<dyn Shape>>(), mem::size_of::<usize>());
+        assert_eq!(mem::size_of::<ThinBlackBox<[u64]>>(), mem::size_of::<usize>());
+        assert_eq!(mem::size_of::<ThinBlackBox<String>>(), mem::size_of::<usize>());
+        assert_eq!(
+            mem::size_of::<Option<ThinBlackBox<dyn Shape>>>(),
+            mem::size_of::<usize>()
+        );
+        assert_eq!(mem::size_of::<Option<ThinBlackBox<[u64]>>>(), mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn trait_object_payload() {
+        let mut shapes: Vec<ThinBlackBox<dyn Shape>> = vec![
+            ThinBlackBox::<dyn Shape>::new_unsize(Square(2), |value| value),
+            ThinBlackBox::<dyn Shape>::new_unsize(Square(3), |value| value),
+        ];
+
+        assert_eq!(shapes.iter().map(|shape| shape.area()).sum::<u32>(), 13);
+        shapes.clear();
+
+        let mut string_box = ThinBlackBox::new("Very large".to_owned());
+        string_box.push_str(" string data");
+        assert_eq!(format!("{:?}", string_box), r#"ThinBlackBox("Very large string data")"#);
+    }
+
+    #[test]
+    fn slice_payload() {
+        let mut slice_box = ThinBlackBox::from_slice(&[1u8, 2, 3]);
+        slice_box[0] = 10;
+        assert_eq!(&*slice_box, [10, 2, 3]);
+
+        let empty = ThinBlackBox::<[String]>::from_vec(Vec::new());
+        assert!(empty.is_empty());
+
+        let strings = ThinBlackBox::from_vec(vec!["a".to_owned(), "b".to_owned()]);
+        assert_eq!(strings.concat(), "ab");
+    }
+
+    #[test]
+    fn drop_runs_exactly_once() {
+        let counter = Cell::new(0);
+
+        drop(ThinBlackBox::from_vec(vec![DropCounter(&counter), DropCounter(&counter)]));
+        assert_eq!(counter.get(), 2);
+
+        let dyn_box =
+            ThinBlackBox::<dyn Unpin + '_>::new_unsize(DropCounter(&counter), |value| value);
+        drop(dyn_box);
+        assert_eq!(counter.get(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "`coerce` must return the value it was given")]
+    fn new_unsize_rejects_other_references() {
+        #[allow(dead_code)]
+        struct Pair(u32, u32);
+
+        let _ = ThinBlackBox::<u32>::new_unsize(Pair(1, 2), |pair| &mut pair.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "`coerce` must return the value it was given")]
+    fn new_unsize_rejects_the_first_field() {
+        #[allow(dead_code)]
+        struct Pair(u32, u32);
+
+        let _ = ThinBlackBox::<u32>::new_unsize(Pair(1, 2), |pair| &mut pair.0);
+    }
+
+    #[test]
+    fn thin_box_moves_across_threads() {
+        let thin_box = ThinBlackBox::from_vec(vec!["a".to_owned(), "b".to_owned()]);
+        let joined = std::thread::spawn(move || thin_box.join(",")).join().unwrap();
+        assert_eq!(joined, "a,b");
+    }
+}
@@ -0,0 +1,86 @@
+//! `BlackBox::try_new*` against a global allocator which fails once the current
+//! thread has allocated more than its budget. It lives in its own test binary, as
+//! the global allocator is process wide.
+
+use raw_pointer_struct_in_rust::{AllocError, BlackBox};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    /// How many bytes this thread may still allocate, `None` means no limit.
+    static BUDGET: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+struct LimitingAllocator;
+
+unsafe impl GlobalAlloc for LimitingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allowed = BUDGET
+            .try_with(|budget| match budget.get() {
+                Some(left) if left < layout.size() => false,
+                Some(left) => {
+                    budget.set(Some(left - layout.size()));
+                    true
+                }
+                None => true,
+            })
+            .unwrap_or(true);
+
+        if allowed {
+            System.alloc(layout)
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LimitingAllocator = LimitingAllocator;
+
+/// Runs `f` with the current thread limited to `bytes` more bytes of allocation.
+fn with_budget<R>(bytes: usize, f: impl FnOnce() -> R) -> R {
+    BUDGET.with(|budget| budget.set(Some(bytes)));
+    let result = f();
+    BUDGET.with(|budget| budget.set(None));
+    result
+}
+
+#[test]
+fn try_new_fails_once_the_budget_is_used_up() {
+    let (first, second) = with_budget(3000, || {
+        (BlackBox::try_new([1u8; 2048]), BlackBox::try_new([2u8; 2048]))
+    });
+
+    assert_eq!(first.unwrap()[0], 1);
+
+    let error = second.unwrap_err();
+    assert_eq!(error.layout(), Layout::new::<[u8; 2048]>());
+    assert_eq!(error.to_string(), "memory allocation of 2048 bytes failed");
+}
+
+#[test]
+fn try_new_with_never_calls_init_when_the_allocation_fails() {
+    let mut called = false;
+    let result: Result<BlackBox<[u64; 512]>, AllocError> = with_budget(0, || {
+        BlackBox::try_new_with(|| {
+            called = true;
+            [0; 512]
+        })
+    });
+
+    assert!(result.is_err());
+    assert!(!called);
+}
+
+#[test]
+fn try_new_uninit_fails_for_a_huge_allocation() {
+    let result = with_budget(1 << 20, BlackBox::<[u8; 64 << 20]>::try_new_uninit);
+    assert_eq!(result.unwrap_err().layout().size(), 64 << 20);
+
+    // Zero-sized types never allocate, so they never fail.
+    assert!(with_budget(0, || BlackBox::try_new(())).is_ok());
+}